@@ -0,0 +1,178 @@
+//! Keyframe animation of scene nodes and morph weights.
+//!
+//! An animation is made up of channels, each of which drives the values of a single target (e.g.
+//! one transform of a node) with a list of keyframes.
+
+use std::str::FromStr;
+
+/// A list of keyframes that animate a single target.
+#[derive(Debug, Clone)]
+pub struct Channel {
+    pub target: Target,
+
+    /// The channel's keyframes, sorted by time.
+    pub keys: Vec<Key>,
+}
+
+impl Channel {
+    /// Returns the time of the channel's first and last keyframes, or `None` if the channel has
+    /// no keyframes.
+    pub fn range(&self) -> Option<(f32, f32)> {
+        match (self.keys.first(), self.keys.last()) {
+            (Some(first), Some(last)) => Some((first.time, last.time)),
+            _ => None,
+        }
+    }
+
+    /// Returns the animated values at `time`.
+    ///
+    /// Times before the first keyframe or after the last keyframe are clamped to the first or
+    /// last keyframe.
+    pub fn sample(&self, time: f32) -> Vec<f32> {
+        let next = match self.keys.iter().position(|key| key.time > time) {
+            Some(0) => { return self.keys[0].values.clone(); }
+            Some(next) => next,
+            None => { return self.keys.last().map(|key| key.values.clone()).unwrap_or_default(); }
+        };
+
+        let from = &self.keys[next - 1];
+        let to = &self.keys[next];
+        match from.interpolation {
+            Interpolation::Step => from.values.clone(),
+
+            Interpolation::Linear => {
+                let t = (time - from.time) / (to.time - from.time);
+                from.values.iter()
+                    .zip(&to.values)
+                    .map(|(&start, &end)| start + (end - start) * t)
+                    .collect()
+            }
+
+            Interpolation::Bezier => {
+                (0..from.values.len().min(to.values.len()))
+                    .map(|index| {
+                        let start = [from.time, from.values[index]];
+                        let end = [to.time, to.values[index]];
+                        let out_tangent = from.out_tangents.get(index).cloned().unwrap_or(start);
+                        let in_tangent = to.in_tangents.get(index).cloned().unwrap_or(end);
+                        bezier(start, out_tangent, in_tangent, end, time)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// The value animated by a channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// The values of one of a node's transforms, given as the index of the node in the scene's
+    /// node list and the index of the transform in the node's transform list.
+    ///
+    /// If `component` is `None` every value of the transform is animated, otherwise only the
+    /// value at that index is, e.g. only the angle of a rotation.
+    Transform {
+        node: usize,
+        transform: usize,
+        component: Option<usize>,
+    },
+
+    /// The weight of one of a morph's targets, given as the index of the morph in the scene's
+    /// morph list and the index of the target in the morph's target list.
+    MorphWeight {
+        morph: usize,
+        target: usize,
+    },
+}
+
+/// A single keyframe.
+#[derive(Debug, Clone)]
+pub struct Key {
+    /// The time of the keyframe, in seconds.
+    pub time: f32,
+    pub values: Vec<f32>,
+
+    /// How values are interpolated between this keyframe and the next one.
+    pub interpolation: Interpolation,
+
+    /// The Bezier control points for each value, as (time, value) pairs. The in tangent controls
+    /// the curve coming into the keyframe, and the out tangent controls the curve leaving it.
+    /// These are empty if the animation has no tangents.
+    pub in_tangents: Vec<[f32; 2]>,
+    pub out_tangents: Vec<[f32; 2]>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    /// The value jumps to the next keyframe's value when its time is reached.
+    Step,
+    Linear,
+
+    /// A cubic Bezier curve through the keyframes' values, shaped by their tangents.
+    Bezier,
+}
+
+/// Evaluates the 2D cubic Bezier curve from `start` to `end` at the point where its time
+/// coordinate equals `time`, returning the value coordinate.
+fn bezier(start: [f32; 2], control_a: [f32; 2], control_b: [f32; 2], end: [f32; 2], time: f32) -> f32 {
+    let point = |s: f32, axis: usize| {
+        let inverse = 1.0 - s;
+        inverse * inverse * inverse * start[axis]
+            + 3.0 * inverse * inverse * s * control_a[axis]
+            + 3.0 * inverse * s * s * control_b[axis]
+            + s * s * s * end[axis]
+    };
+
+    // The curve's time coordinate increases monotonically for any sensible animation curve, so
+    // the curve parameter for `time` can be found by bisection.
+    let (mut low, mut high) = (0.0, 1.0);
+    for _ in 0..32 {
+        let middle = (low + high) / 2.0;
+        if point(middle, 0) < time {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+
+    point((low + high) / 2.0, 1)
+}
+
+/// A range of an animation to play, in seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClipRange {
+    pub start: f32,
+    pub end: f32,
+}
+
+impl ClipRange {
+    /// Wraps `time` into the range, so that playing past the end loops back to the start and
+    /// scrubbing back past the start loops around to the end.
+    pub fn wrap(&self, time: f32) -> f32 {
+        let length = self.end - self.start;
+        if length <= 0.0 {
+            return self.start;
+        }
+
+        let offset = (time - self.start) % length;
+        if offset < 0.0 { self.start + offset + length } else { self.start + offset }
+    }
+}
+
+impl FromStr for ClipRange {
+    type Err = String;
+
+    /// Parses a range written as `start:end`, e.g. `1.5:4`.
+    fn from_str(string: &str) -> Result<ClipRange, String> {
+        let error = || format!("Invalid clip range {:?}, expected start:end in seconds", string);
+
+        let mut parts = string.splitn(2, ':');
+        let start = parts.next().and_then(|start| start.trim().parse().ok()).ok_or_else(&error)?;
+        let end = parts.next().and_then(|end| end.trim().parse().ok()).ok_or_else(&error)?;
+        if end < start {
+            return Err(format!("Invalid clip range {:?}, the end is before the start", string));
+        }
+
+        Ok(ClipRange { start, end })
+    }
+}
@@ -1,163 +1,2443 @@
+use animation::{self, Interpolation, Target};
+use collaborate;
 use collaborate::v1_4::*;
 use collaborate::v1_4::Mesh as ColladaMesh;
-use polygon::geometry::mesh::Mesh as PolygonMesh;
-use polygon::geometry::mesh::MeshBuilder;
-use polygon::geometry::mesh::Vertex as PolygonVertex;
-use polygon::math::*;
+use collaborate::v1_4::Node as ColladaNode;
+use loader::{self, Loader};
+use matrix::Matrix;
+use mesh::{MeshData, Vertex};
+use normals;
+use polygon::math::{Color, Point, Vector2, Vector3};
+use scene::{self, Geometry, GeometryInstance, LightKind, LoadOptions, Scene, Shading, Transform, TransformKind, UpAxis};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
 use std::fs::File;
+use std::io::{self, Read};
 use std::path::Path;
+use texture::{self, Texture};
+use triangulate;
+use xml::reader::{EventReader, XmlEvent};
 
-pub fn load_mesh<P: AsRef<Path>>(path: P) -> Result<PolygonMesh, &'static str> {
-    let file = File::open(path).expect("Failed to open file");
-    let document = Collada::read(file).expect("Failed to parse COLLADA document");
+/// An error that occurred while loading a COLLADA document.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The file couldn't be opened.
+    Io(io::Error),
 
+    /// The file isn't a valid COLLADA document.
+    Parse(collaborate::Error),
+
+    /// The file isn't well-formed XML, so its COLLADA version couldn't be determined.
+    Xml(xml::reader::Error),
+
+    /// The document's `version` attribute names a version of COLLADA that isn't supported. Only
+    /// 1.4.x and 1.5.x documents can be loaded.
+    UnsupportedVersion {
+        version: String,
+    },
+
+    /// The document doesn't contain any meshes that could be loaded.
+    NoMeshes,
+
+    /// A `VERTEX` input referenced a `<vertices>` element that doesn't belong to the input's
+    /// mesh.
+    ForeignVertices {
+        expected: String,
+        found: String,
+    },
+
+    /// A `<vertices>` element had no input with the `POSITION` semantic.
+    MissingPositionInput {
+        vertices: String,
+    },
+
+    /// An input referenced a `<source>` that doesn't exist in the parent mesh.
+    MissingSource {
+        id: String,
+        semantic: String,
+    },
+
+    /// A `<source>` had no `<technique_common>` accessor.
+    MissingAccessor {
+        id: String,
+        semantic: String,
+    },
+
+    /// A `<source>` didn't have the type of array required by the semantic it was used for,
+    /// e.g. a `POSITION` source with a Name array.
+    UnsupportedArray {
+        id: String,
+        semantic: String,
+    },
+
+    /// An index referenced an element past the end of a `<source>`, as given by the `count` of
+    /// the source's accessor.
+    IndexOutOfRange {
+        id: String,
+        semantic: String,
+        index: usize,
+        count: usize,
+    },
+
+    /// A `<source>` accessor's offset, stride, and count describe elements past the end of the
+    /// source's array.
+    InvalidAccessor {
+        id: String,
+        semantic: String,
+    },
+
+    /// A `<source>` accessor didn't have a param for one of the components required by the
+    /// semantic, e.g. a `NORMAL` source without a `Z` param.
+    MissingComponent {
+        id: String,
+        semantic: String,
+        component: &'static str,
+    },
+
+    /// A primitive's `<p>` elements don't contain a valid number of indices for the primitive's
+    /// inputs, e.g. a `<polylist>` whose `<vcount>` doesn't match its `<p>`.
+    InvalidIndices {
+        geometry: Option<String>,
+        primitive: &'static str,
+    },
+
+    /// A vertex in a primitive had no `POSITION` data.
+    MissingPosition {
+        geometry: Option<String>,
+    },
+
+    /// The document's `<scene>` instances a `<visual_scene>` that doesn't exist.
+    MissingVisualScene {
+        id: String,
+    },
+
+    /// A node has an `<instance_geometry>` that references a geometry that doesn't exist.
+    MissingGeometry {
+        node: Option<String>,
+        id: String,
+    },
+
+    /// A node has an `<instance_camera>` that references a camera that doesn't exist.
+    MissingCamera {
+        node: Option<String>,
+        id: String,
+    },
+
+    /// A node has an `<instance_light>` that references a light that doesn't exist.
+    MissingLight {
+        node: Option<String>,
+        id: String,
+    },
+
+    /// A node has an `<instance_node>` that references a node that doesn't exist.
+    MissingNode {
+        node: Option<String>,
+        id: String,
+    },
+
+    /// A `<bind_material>` or a `<material>` references a material or effect that doesn't
+    /// exist.
+    MissingMaterial {
+        id: String,
+    },
+
+    /// A node directly or indirectly instances itself.
+    RecursiveNode {
+        id: String,
+    },
+
+    /// A node has an `<instance_controller>` that references a controller that doesn't exist.
+    MissingController {
+        node: Option<String>,
+        id: String,
+    },
+
+    /// A `<skin>` controller is malformed, e.g. its joint and weight data don't match up or it
+    /// skins a geometry that doesn't exist.
+    InvalidSkin {
+        controller: Option<String>,
+        reason: &'static str,
+    },
+
+    /// A `<morph>` controller is malformed, e.g. it has a different number of targets and
+    /// weights or one of its targets doesn't exist.
+    InvalidMorph {
+        controller: Option<String>,
+        reason: &'static str,
+    },
+
+    /// A skin is instanced, but one of its joints doesn't match any node in the scene.
+    MissingJoint {
+        controller: Option<String>,
+        joint: String,
+    },
+
+    /// An `<animation>` is malformed, e.g. a channel references a sampler that doesn't exist or
+    /// a sampler has a different number of input and output values.
+    InvalidAnimation {
+        animation: Option<String>,
+        reason: &'static str,
+    },
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Io(ref error) => write!(f, "Failed to open file: {}", error),
+            LoadError::Parse(ref error) => write!(f, "Failed to parse COLLADA document: {}", error),
+            LoadError::Xml(ref error) => write!(f, "Failed to read XML: {}", error),
+
+            LoadError::UnsupportedVersion { ref version } => write!(
+                f,
+                "Unsupported COLLADA version {:?}, only 1.4.x and 1.5.x are supported",
+                version,
+            ),
+
+            LoadError::NoMeshes => write!(f, "No meshes found in the document"),
+
+            LoadError::ForeignVertices { ref expected, ref found } => write!(
+                f,
+                "Input targets vertices {:?}, but the parent mesh's vertices are {:?}",
+                found,
+                expected,
+            ),
+
+            LoadError::MissingPositionInput { ref vertices } => write!(
+                f,
+                "Vertices {:?} have no input with the \"POSITION\" semantic",
+                vertices,
+            ),
+
+            LoadError::MissingSource { ref id, ref semantic } => write!(
+                f,
+                "No source with ID {:?} (used for {:?}) in the parent mesh",
+                id,
+                semantic,
+            ),
+
+            LoadError::MissingAccessor { ref id, ref semantic } => write!(
+                f,
+                "Source {:?} (used for {:?}) has no accessor",
+                id,
+                semantic,
+            ),
+
+            LoadError::UnsupportedArray { ref id, ref semantic } => write!(
+                f,
+                "Source {:?} (used for {:?}) doesn't have a numeric array",
+                id,
+                semantic,
+            ),
+
+            LoadError::IndexOutOfRange { ref id, ref semantic, index, count } => write!(
+                f,
+                "Index {} is out of range for source {:?} (used for {:?}), which has {} elements",
+                index,
+                id,
+                semantic,
+                count,
+            ),
+
+            LoadError::InvalidAccessor { ref id, ref semantic } => write!(
+                f,
+                "Accessor of source {:?} (used for {:?}) reads past the end of its array",
+                id,
+                semantic,
+            ),
+
+            LoadError::MissingComponent { ref id, ref semantic, component } => write!(
+                f,
+                "Source {:?} (used for {:?}) has no {} component",
+                id,
+                semantic,
+                component,
+            ),
+
+            LoadError::InvalidIndices { ref geometry, primitive } => write!(
+                f,
+                "<{}> in geometry {:?} has the wrong number of indices for its inputs",
+                primitive,
+                geometry,
+            ),
+
+            LoadError::MissingPosition { ref geometry } => write!(
+                f,
+                "Vertex in geometry {:?} is missing position attribute",
+                geometry,
+            ),
+
+            LoadError::MissingVisualScene { ref id } => write!(
+                f,
+                "Document instances visual scene {:?}, but no such visual scene exists",
+                id,
+            ),
+
+            LoadError::MissingGeometry { ref node, ref id } => write!(
+                f,
+                "Node {:?} instances geometry {:?}, but no such geometry exists",
+                node,
+                id,
+            ),
+
+            LoadError::MissingCamera { ref node, ref id } => write!(
+                f,
+                "Node {:?} instances camera {:?}, but no such camera exists",
+                node,
+                id,
+            ),
+
+            LoadError::MissingLight { ref node, ref id } => write!(
+                f,
+                "Node {:?} instances light {:?}, but no such light exists",
+                node,
+                id,
+            ),
+
+            LoadError::MissingNode { ref node, ref id } => write!(
+                f,
+                "Node {:?} instances node {:?}, but no such node exists",
+                node,
+                id,
+            ),
+
+            LoadError::MissingMaterial { ref id } => write!(
+                f,
+                "Material or effect {:?} is referenced but doesn't exist",
+                id,
+            ),
+
+            LoadError::RecursiveNode { ref id } => write!(f, "Node {:?} instances itself", id),
+
+            LoadError::MissingController { ref node, ref id } => write!(
+                f,
+                "Node {:?} instances controller {:?}, but no such controller exists",
+                node,
+                id,
+            ),
+
+            LoadError::InvalidSkin { ref controller, reason } => write!(
+                f,
+                "Skin controller {:?} is invalid: {}",
+                controller,
+                reason,
+            ),
+
+            LoadError::InvalidMorph { ref controller, reason } => write!(
+                f,
+                "Morph controller {:?} is invalid: {}",
+                controller,
+                reason,
+            ),
+
+            LoadError::MissingJoint { ref controller, ref joint } => write!(
+                f,
+                "Skin controller {:?} has joint {:?}, but no node in the skeleton matches it",
+                controller,
+                joint,
+            ),
+
+            LoadError::InvalidAnimation { ref animation, reason } => write!(
+                f,
+                "Animation {:?} is invalid: {}",
+                animation,
+                reason,
+            ),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn description(&self) -> &str {
+        match *self {
+            LoadError::Io(..) => "Failed to open file",
+            LoadError::Parse(..) => "Failed to parse COLLADA document",
+            LoadError::Xml(..) => "Failed to read XML",
+            LoadError::UnsupportedVersion { .. } => "Unsupported COLLADA version",
+            LoadError::NoMeshes => "No meshes found in the document",
+            LoadError::ForeignVertices { .. } => "Input targets vertices from a different mesh",
+            LoadError::MissingPositionInput { .. } => "Vertices have no POSITION input",
+            LoadError::MissingSource { .. } => "Input references a missing source",
+            LoadError::MissingAccessor { .. } => "Source has no accessor",
+            LoadError::UnsupportedArray { .. } => "Source has an unsupported array type",
+            LoadError::IndexOutOfRange { .. } => "Index is out of range for its source",
+            LoadError::InvalidAccessor { .. } => "Accessor reads past the end of its array",
+            LoadError::MissingComponent { .. } => "Source is missing a component",
+            LoadError::InvalidIndices { .. } => "Primitive has the wrong number of indices",
+            LoadError::MissingPosition { .. } => "Vertex is missing position attribute",
+            LoadError::MissingVisualScene { .. } => "Document instances a missing visual scene",
+            LoadError::MissingGeometry { .. } => "Node instances a missing geometry",
+            LoadError::MissingCamera { .. } => "Node instances a missing camera",
+            LoadError::MissingLight { .. } => "Node instances a missing light",
+            LoadError::MissingNode { .. } => "Node instances a missing node",
+            LoadError::MissingMaterial { .. } => "Reference to a missing material or effect",
+            LoadError::RecursiveNode { .. } => "Node instances itself",
+            LoadError::MissingController { .. } => "Node instances a missing controller",
+            LoadError::InvalidSkin { .. } => "Skin controller is invalid",
+            LoadError::InvalidMorph { .. } => "Morph controller is invalid",
+            LoadError::MissingJoint { .. } => "Skin joint doesn't match any node",
+            LoadError::InvalidAnimation { .. } => "Animation is invalid",
+        }
+    }
+
+    fn cause(&self) -> Option<&dyn Error> {
+        match *self {
+            LoadError::Io(ref error) => Some(error),
+            LoadError::Parse(ref error) => Some(error),
+            LoadError::Xml(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(from: io::Error) -> LoadError {
+        LoadError::Io(from)
+    }
+}
+
+impl From<collaborate::Error> for LoadError {
+    fn from(from: collaborate::Error) -> LoadError {
+        LoadError::Parse(from)
+    }
+}
+
+impl From<xml::reader::Error> for LoadError {
+    fn from(from: xml::reader::Error) -> LoadError {
+        LoadError::Xml(from)
+    }
+}
+
+/// The COLLADA loader, for the loader registry.
+pub struct ColladaLoader;
+
+impl Loader for ColladaLoader {
+    fn name(&self) -> &'static str {
+        "collada"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["dae"]
+    }
+
+    fn detect(&self, header: &[u8]) -> bool {
+        // COLLADA documents are XML, so the root element may come after the XML declaration
+        // and any comments.
+        let header = loader::trim_start(header);
+        (header.starts_with(b"<?xml") || header.starts_with(b"<COLLADA")) && loader::contains(header, b"<COLLADA")
+    }
+
+    fn load(&self, path: &Path, options: &LoadOptions) -> Result<Scene, Box<dyn Error>> {
+        load_scene(path, options).map_err(Into::into)
+    }
+}
+
+/// Loads the COLLADA document at `path` as a scene.
+///
+/// Every `<geometry>` element in the document is loaded, and the node hierarchy is built from the
+/// `<visual_scene>` instanced by the document's `<scene>` element. Documents without a visual
+/// scene get a single root node that instances every geometry.
+///
+/// Both COLLADA 1.4 and 1.5 documents are supported. The version is taken from the `version`
+/// attribute of the root element, and 1.5 documents are converted into their 1.4 equivalent
+/// before they're loaded, so that both versions produce the same scene.
+pub fn load_scene<P: AsRef<Path>>(path: P, options: &LoadOptions) -> Result<Scene, LoadError> {
+    let path = path.as_ref();
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+    let bytes = match document_version(&bytes)? {
+        Some(ref version) if version.starts_with("1.5") => downgrade_1_5(&bytes)?,
+        Some(ref version) if !version.starts_with("1.4") => {
+            return Err(LoadError::UnsupportedVersion { version: version.clone() });
+        }
+
+        // Documents without a version are left to the parser to reject.
+        _ => bytes,
+    };
+    let document = Collada::read(&*bytes)?;
+
+    let mut scene = Scene::default();
+    scene.geometries = load_geometries(&document, options)?;
+
+    // Textures are referenced relative to the document.
+    let base = path.parent().unwrap_or(Path::new(""));
+    let bump_textures = bump_textures(&bytes)?;
+    let (materials, textures) = load_materials(&document, &bump_textures, base)?;
+    scene.materials = materials;
+    scene.textures = textures;
+    scene.lights = load_lights(&document);
+
+    // Convert the geometry from the document's coordinate system into the viewer's, preferring
+    // any overrides given in the options since some exporters write incorrect metadata.
+    let up_axis = options.up_axis.unwrap_or(match document.asset.up_axis {
+        collaborate::v1_4::UpAxis::X => UpAxis::X,
+        collaborate::v1_4::UpAxis::Y => UpAxis::Y,
+        collaborate::v1_4::UpAxis::Z => UpAxis::Z,
+    });
+    let meters_per_unit = options.meters_per_unit.unwrap_or(document.asset.unit.meter as f32);
+    scene.basis = up_axis.basis(meters_per_unit);
+    scene.cameras = load_cameras(&document, meters_per_unit);
+    for geometry in &mut scene.geometries {
+        for mesh in &mut geometry.meshes {
+            mesh.transform(&scene.basis);
+        }
+    }
+    let (morphs, morph_weights) = load_morphs(&document, &scene.geometries)?;
+    scene.morphs = morphs;
+    scene.skins = load_skins(&document, &scene.geometries, &scene.morphs, scene.basis)?;
+
+    // Find the visual scene to load, falling back to the first visual scene if the document
+    // doesn't specify one.
+    let visual_scenes = document.libraries()
+        .filter_map(Library::as_library_visual_scenes)
+        .flat_map(|library| library.visual_scenes())
+        .collect::<Vec<_>>();
+    let instance = document.scene.as_ref()
+        .and_then(|scene| scene.instance_visual_scene.as_ref());
+    let visual_scene = match instance {
+        Some(instance) => {
+            let id = instance.url.id();
+            let visual_scene = visual_scenes.iter()
+                .find(|visual_scene| visual_scene.id.as_ref().map(String::as_str) == Some(id))
+                .ok_or_else(|| LoadError::MissingVisualScene { id: id.into() })?;
+            Some(*visual_scene)
+        }
+
+        None => visual_scenes.first().cloned(),
+    };
+
+    match visual_scene {
+        Some(visual_scene) => {
+            // Any node in the document can be the target of an `<instance_node>`, not just the
+            // ones in `<library_nodes>`.
+            let mut nodes = HashMap::new();
+            for library in document.libraries().filter_map(Library::as_library_nodes) {
+                for node in library.nodes() {
+                    collect_nodes(node, &mut nodes);
+                }
+            }
+            for visual_scene in &visual_scenes {
+                for node in &visual_scene.nodes {
+                    collect_nodes(node, &mut nodes);
+                }
+            }
+
+            let skeletons = {
+                let mut builder = NodeBuilder {
+                    scene: &mut scene,
+                    nodes,
+                    instancing: Vec::new(),
+                    skeletons: Vec::new(),
+                };
+                for node in &visual_scene.nodes {
+                    builder.add_node(node, None)?;
+                }
+
+                builder.skeletons
+            };
+
+            // Joints can be anywhere in the hierarchy, so they can only be resolved once every
+            // node has been added.
+            for (node, instance, roots) in skeletons {
+                let skin = scene.nodes[node].skins[instance].skin;
+                let joints = resolve_joints(&scene, skin, &roots)?;
+                scene.nodes[node].skins[instance].joints = joints;
+            }
+        }
+
+        None => {
+            let geometries = (0..scene.geometries.len())
+                .map(|geometry| GeometryInstance { geometry, materials: HashMap::new() })
+                .collect();
+            scene.nodes.push(scene::Node { geometries, .. scene::Node::default() });
+        }
+    }
+
+    scene.channels = load_animations(&document, &scene, &morph_weights)?;
+
+    Ok(scene)
+}
+
+/// Returns the value of the `version` attribute of the document's root element, if it has one.
+fn document_version(bytes: &[u8]) -> Result<Option<String>, LoadError> {
+    for event in EventReader::new(bytes) {
+        if let XmlEvent::StartElement { attributes, .. } = event? {
+            let version = attributes.into_iter()
+                .find(|attribute| attribute.name.prefix.is_none() && attribute.name.local_name == "version")
+                .map(|attribute| attribute.value);
+            return Ok(version);
+        }
+    }
+
+    Ok(None)
+}
+
+/// Converts a COLLADA 1.5 document into an equivalent COLLADA 1.4 document.
+///
+/// The parts of the schema used by the viewer barely changed between the versions, so the
+/// conversion only has to rewrite the few elements that did change:
+///
+/// - `<init_from>` in an `<image>` wraps its URI in a `<ref>` element.
+/// - `<sampler2D>` references its image directly with `<instance_image>`, where 1.4 goes through
+///   a `<surface>` param. The image ID is written as the sampler's `<source>`, which
+///   `TextureCache::resolve()` accepts in place of a surface.
+/// - Some elements were renamed or added, and are renamed or dropped to match the 1.4 schema.
+///
+/// Elements that only exist in 1.5 and have no 1.4 equivalent, e.g. the kinematics libraries,
+/// are dropped with a warning.
+fn downgrade_1_5(bytes: &[u8]) -> Result<Vec<u8>, LoadError> {
+    /// Each open element of the source document, and whether its tags are written to the
+    /// converted document.
+    struct Open {
+        name: String,
+        written: bool,
+    }
+
+    let mut output = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    let mut open = Vec::<Open>::new();
+    let mut skip_depth = 0;
+    for event in EventReader::new(bytes) {
+        match event? {
+            XmlEvent::StartElement { name, attributes, .. } => {
+                if skip_depth > 0 {
+                    skip_depth += 1;
+                    continue;
+                }
+
+                let local_name = name.local_name;
+                let parent = open.last().map(|parent| parent.name.clone()).unwrap_or_default();
+                let attribute = |name: &str| {
+                    attributes.iter()
+                        .find(|attribute| attribute.name.prefix.is_none() && attribute.name.local_name == name)
+                        .map(|attribute| attribute.value.as_str())
+                };
+
+                // Drop elements that 1.4 doesn't have, warning about the ones that would have
+                // affected the scene. The sampler filters exist in both versions but take
+                // different values, and the viewer doesn't use them anyway.
+                let dropped = match (&*parent, &*local_name) {
+                    ("COLLADA", "library_formulas")
+                    | ("COLLADA", "library_kinematics_models")
+                    | ("COLLADA", "library_kinematics_scenes")
+                    | ("COLLADA", "library_articulated_systems")
+                    | ("COLLADA", "library_joints")
+                    | ("scene", "instance_kinematics_scene") => {
+                        println!("WARNING: COLLADA 1.5 <{}> isn't supported, ignoring it", local_name);
+                        true
+                    }
+
+                    ("init_from", "hex") => {
+                        println!("WARNING: Embedded image data isn't supported, ignoring it");
+                        true
+                    }
+
+                    ("asset", "coverage")
+                    | ("contributor", "author_email")
+                    | ("contributor", "author_website")
+                    | ("image", "renderable")
+                    | ("image", "create_2d")
+                    | ("image", "create_3d")
+                    | ("image", "create_cube")
+                    | ("sampler2D", "minfilter")
+                    | ("sampler2D", "magfilter")
+                    | ("sampler2D", "mipfilter")
+                    | ("sampler2D", "mip_min_level")
+                    | ("sampler2D", "max_anisotropy") => true,
+
+                    _ => false,
+                };
+                if dropped {
+                    skip_depth = 1;
+                    continue;
+                }
+
+                // The root element is rewritten with the 1.4 namespace and version.
+                if open.is_empty() {
+                    output.push_str("<COLLADA xmlns=\"http://www.collada.org/2005/11/COLLADASchema\" version=\"1.4.1\">");
+                    open.push(Open { name: local_name, written: true });
+                    continue;
+                }
+
+                match (&*parent, &*local_name) {
+                    // The URI of the image is written directly into the `<init_from>`.
+                    ("init_from", "ref") => {
+                        open.push(Open { name: local_name.clone(), written: false });
+                        continue;
+                    }
+
+                    ("sampler2D", "instance_image") => {
+                        let id = attribute("url").map(|url| url.trim_start_matches('#')).unwrap_or("");
+                        output.push_str("<source>");
+                        push_escaped(&mut output, id);
+                        output.push_str("</source>");
+                        open.push(Open { name: local_name.clone(), written: false });
+                        continue;
+                    }
+
+                    _ => {}
+                }
+
+                let written_name = match (&*parent, &*local_name) {
+                    ("sampler2D", "mip_max_level") => "mipmap_maxlevel",
+                    ("sampler2D", "mip_bias") => "mipmap_bias",
+                    _ => &*local_name,
+                }.to_string();
+
+                output.push('<');
+                output.push_str(&written_name);
+
+                // The 1.5 `<init_from>` of an image has attributes for generating mipmaps, which
+                // 1.4 doesn't have.
+                if !(parent == "image" && local_name == "init_from") {
+                    for attribute in attributes.iter().filter(|attribute| attribute.name.prefix.is_none()) {
+                        output.push(' ');
+                        output.push_str(&attribute.name.local_name);
+                        output.push_str("=\"");
+                        push_escaped(&mut output, &attribute.value);
+                        output.push('"');
+                    }
+                }
+
+                output.push('>');
+                open.push(Open { name: written_name, written: true });
+            }
+
+            XmlEvent::EndElement { .. } => {
+                if skip_depth > 0 {
+                    skip_depth -= 1;
+                    continue;
+                }
+
+                if let Some(element) = open.pop() {
+                    if element.written {
+                        output.push_str("</");
+                        output.push_str(&element.name);
+                        output.push('>');
+                    }
+                }
+            }
+
+            XmlEvent::Characters(ref text) | XmlEvent::CData(ref text) => {
+                if skip_depth == 0 {
+                    push_escaped(&mut output, text);
+                }
+            }
+
+            // The whitespace around a `<ref>` would otherwise end up in the image's URI.
+            XmlEvent::Whitespace(ref text) => {
+                let in_init_from = open.last().map_or(false, |element| element.name == "init_from");
+                if skip_depth == 0 && !in_init_from {
+                    output.push_str(text);
+                }
+            }
+
+            _ => {}
+        }
+    }
+
+    Ok(output.into_bytes())
+}
+
+/// Appends `text` to an XML document, escaping the characters that can't appear in text or
+/// attribute values.
+fn push_escaped(output: &mut String, text: &str) {
+    for character in text.chars() {
+        match character {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            _ => output.push(character),
+        }
+    }
+}
+
+/// Loads every mesh in every `<geometry>` element of the document.
+fn load_geometries(document: &Collada, options: &LoadOptions) -> Result<Vec<Geometry>, LoadError> {
+    let mut geometries = Vec::new();
     for library in document.libraries().filter_map(Library::as_library_geometries) {
-        let meshes = library.geometries()
-            .filter_map(|geometry| geometry.geometric_element.as_mesh());
-        for mesh in meshes {
-            for polylist in mesh.primitives().filter_map(Primitive::as_polylist) {
-                let mesh = process_polylist(mesh, polylist)?;
-
-                // TODO: Support loading multiple meshes.
-                return Ok(mesh);
-            }
-        }
-    }
-
-    Err("No meshes found in the document I guess")
-}
-
-pub fn process_polylist(mesh: &ColladaMesh, polylist: &Polylist) -> Result<PolygonMesh, &'static str> {
-    let mut builder = MeshBuilder::new();
-    let mut indices = Vec::new();
-
-    for polygon in polylist {
-        for vertex in &polygon {
-            let mut position = None;
-            let mut normal = None;
-            let texcoord = Vec::new();
-
-            // For each of the attributes in the vertex, find the correct input and then grab
-            // the vertex data.
-            for attribute in vertex {
-                // Retrieve the raw data for each attribute that matches the attribute's offset.
-                for input in polylist.inputs_for_offset(attribute.offset) {
-                    // Handle the input based on its semantic.
-                    match input.semantic.as_ref() {
-                        // The "VERTEX" semantic means that this input indexes into all
-                        // sources specified in the `vertices` member of the host mesh.
-                        "VERTEX" => {
-                            // We're assuming that the input refers to the mesh's `vertices`
-                            // member. If that assumption is incorrect, we're going to produce
-                            // the wrong mesh data.
-                            assert_eq!(
-                                mesh.vertices.id,
-                                input.source.id(),
-                                "Input targets a `Vertices` that doesn't belong to same mesh",
-                            );
-
-                            // Find the input that corresponds to the "POSITION" semantic. The
-                            // COLLADA spec requires that there be one in a `<vertices>` element.
-                            let input = mesh.vertices.inputs.iter()
-                                .find(|input| input.semantic == "POSITION")
-                                .expect("Vertices had no input with the \"POSITION\" semantic");
-
-                            // Find the mesh source identified by the input's `source` within the
-                            // parent `Mesh` object.
-                            let source = mesh.find_source(input.source.id())
-                                .expect("Didn't find a source with a matching ID in the parent mesh");
-
-                            // Retrieve the source's accessor and raw float array. We only support
-                            // using floats for position and normal source data, so we ignore
-                            // any other type of array source.
-                            let accessor = &source.common_accessor().expect("Source has no accessor");
-                            let array = source.array.as_ref()
-                                .and_then(Array::as_float_array)
-                                .expect("Source wasn't a float array");
-
-                            /// Use the accessor to get the position data for the current vertex.
-                            let position_data = accessor.access(array.data.as_ref(), attribute.index);
-
-                            // Use the `params` in the accesor to determine which elements in
-                            // `normal_data` correspond to the normal's X, Y, and Z components.
-                            let mut x = None;
-                            let mut y = None;
-                            let mut z = None;
-
-                            for (param, &position_component) in accessor.params.iter().zip(position_data.iter()) {
-                                match param.name.as_ref().map(String::as_str) {
-                                    Some("X") => { x = Some(position_component); }
-                                    Some("Y") => { y = Some(position_component); }
-                                    Some("Z") => { z = Some(position_component); }
-
-                                    // Ignore any unrecognized or unsupported names.
-                                    _ => {}
-                                }
-                            }
-
-                            position = Some(Point::new(
-                                x.expect("Normal had no X component"),
-                                y.expect("Normal had no Y component"),
-                                z.expect("Normal had no Z component"),
-                            ))
-                        }
+        for geometry in library.geometries() {
+            let mesh = match geometry.geometric_element.as_mesh() {
+                Some(mesh) => mesh,
+                None => { continue; }
+            };
 
-                        "NORMAL" => {
-                            // Find the mesh source identified by the input's `source` within the
-                            // parent `Mesh` object.
-                            let source = mesh.find_source(input.source.id())
-                                .expect("Didn't find a source with a matching ID in the parent mesh");
-
-                            // Retrieve the source's accessor and raw float array. We only support
-                            // using floats for position and normal source data, so we ignore
-                            // any other type of array source.
-                            let accessor = &source.common_accessor().expect("Source has no accessor");
-                            let array = source.array.as_ref()
-                                .and_then(Array::as_float_array)
-                                .expect("Source wasn't a float array");
-
-                            /// Use the accessor to get the normal data for the current vertex.
-                            let normal_data = accessor.access(array.data.as_ref(), attribute.index);
-
-                            // Use the `params` in the accesor to determine which elements in
-                            // `normal_data` correspond to the normal's X, Y, and Z components.
-                            let mut x = None;
-                            let mut y = None;
-                            let mut z = None;
-
-                            for (param, &normal_component) in accessor.params.iter().zip(normal_data.iter()) {
-                                match param.name.as_ref().map(String::as_str) {
-                                    Some("X") => { x = Some(normal_component); }
-                                    Some("Y") => { y = Some(normal_component); }
-                                    Some("Z") => { z = Some(normal_component); }
-
-                                    // Ignore any unrecognized or unsupported names.
-                                    _ => {}
-                                }
-                            }
-
-                            normal = Some(Vector3 {
-                                x: x.expect("Normal had no X component"),
-                                y: y.expect("Normal had no Y component"),
-                                z: z.expect("Normal had no Z component"),
-                            })
-                        }
+            let mut meshes = Vec::new();
+            for primitive in mesh.primitives() {
+                if let Some(faces) = primitive_faces(geometry.id.as_ref(), primitive)? {
+                    let mut data = process_faces(geometry.id.as_ref(), mesh, &faces, options)?;
+
+                    // Normals are generated if any vertex is missing one, since a mesh that's
+                    // only partially lit would look broken either way.
+                    if options.regenerate_normals || data.vertices.iter().any(|vertex| vertex.normal.is_none()) {
+                        normals::generate(&mut data, options.normal_mode, options.crease_angle);
+                    }
+
+                    meshes.push(data);
+                }
+            }
+
+            geometries.push(Geometry {
+                id: geometry.id.clone(),
+                name: geometry.name.clone(),
+                meshes,
+            });
+        }
+    }
+
+    if geometries.iter().all(|geometry| geometry.meshes.is_empty()) {
+        return Err(LoadError::NoMeshes);
+    }
+
+    Ok(geometries)
+}
+
+/// Loads every `<material>` in the document, resolving each one's effect into a material
+/// description.
+///
+/// Only `<profile_COMMON>` effects are supported. Materials whose effect has no common profile
+/// get the default material description. Returns the materials along with the textures that they
+/// reference.
+fn load_materials(
+    document: &Collada,
+    bump_textures: &HashMap<String, String>,
+    base: &Path,
+) -> Result<(Vec<scene::Material>, Vec<Texture>), LoadError> {
+    let effects = document.libraries()
+        .filter_map(Library::as_library_effects)
+        .flat_map(|library| library.effects())
+        .collect::<Vec<_>>();
+    let images = document.libraries()
+        .filter_map(Library::as_library_images)
+        .flat_map(|library| library.images())
+        .collect::<Vec<_>>();
+
+    let mut textures = TextureCache {
+        base,
+        images: &images,
+        textures: Vec::new(),
+        loaded: HashMap::new(),
+    };
+
+    let mut materials = Vec::new();
+    for library in document.libraries().filter_map(Library::as_library_materials) {
+        for material in library.materials() {
+            let effect_id = material.instance_effect.url.id();
+            let effect = effects.iter()
+                .find(|effect| effect.id == effect_id)
+                .ok_or_else(|| LoadError::MissingMaterial { id: effect_id.into() })?;
+
+            let mut result = scene::Material {
+                id: material.id.clone(),
+                name: material.name.clone(),
+                .. scene::Material::default()
+            };
+
+            if let Some(profile) = effect.profiles.iter().filter_map(Profile::as_profile_common).next() {
+                process_technique(&profile.technique, &mut result);
+
+                // Textures are referenced through the profile's params, so they have to be
+                // resolved in the context of the profile.
+                let (diffuse, specular) = match profile.technique.shader {
+                    Shader::Constant(..) => (&None, &None),
+                    Shader::Lambert(ref lambert) => (&lambert.diffuse, &None),
+                    Shader::Phong(ref phong) => (&phong.diffuse, &phong.specular),
+                    Shader::Blinn(ref blinn) => (&blinn.diffuse, &blinn.specular),
+                };
+                result.diffuse_texture = textures.resolve(profile, diffuse);
+                result.specular_texture = textures.resolve(profile, specular);
+                result.normal_texture = bump_textures.get(effect_id)
+                    .map(|sampler| textures.resolve_name(profile, sampler));
+            }
+
+            materials.push(result);
+        }
+    }
+
+    Ok((materials, textures.textures))
+}
+
+/// Finds the normal map of each effect, returning the `texture` attribute of the map's
+/// `<texture>` keyed by the ID of the effect.
+///
+/// COLLADA has no standard slot for normal maps, so exporters write them as a `<bump>` texture in
+/// an `<extra>` technique of their own profile. collaborate doesn't expose the contents of
+/// `<extra>` elements, so they're read from the XML directly.
+fn bump_textures(bytes: &[u8]) -> Result<HashMap<String, String>, LoadError> {
+    /// Profiles whose `<bump>` texture is known to be a normal map.
+    fn is_supported_profile(profile: &str) -> bool {
+        profile == "FCOLLADA" || profile == "MAX3D" || profile.starts_with("OpenCOLLADA")
+    }
+
+    let mut bump_textures = HashMap::new();
+    let mut effect = None;
+
+    // The local name and `profile` attribute of each open element.
+    let mut open = Vec::<(String, Option<String>)>::new();
+    for event in EventReader::new(bytes) {
+        match event? {
+            XmlEvent::StartElement { name, attributes, .. } => {
+                let attribute = |name: &str| {
+                    attributes.iter()
+                        .find(|attribute| attribute.name.prefix.is_none() && attribute.name.local_name == name)
+                        .map(|attribute| attribute.value.clone())
+                };
 
-                        // Ignore any unknown semantics.
-                        semantic @ _ => { println!("Ignoring unknown semantic {:?}", semantic); }
+                if name.local_name == "effect" {
+                    effect = attribute("id");
+                } else if name.local_name == "texture" && open.len() >= 3 {
+                    let parents = &open[open.len() - 3..];
+                    let in_bump = parents[0].0 == "extra"
+                        && parents[1].0 == "technique"
+                        && parents[2].0 == "bump"
+                        && parents[1].1.as_ref().map_or(false, |profile| is_supported_profile(profile));
+                    if let (true, Some(effect), Some(texture)) = (in_bump, effect.as_ref(), attribute("texture")) {
+                        bump_textures.entry(effect.clone()).or_insert(texture);
                     }
                 }
+
+                open.push((name.local_name, attribute("profile")));
+            }
+
+            XmlEvent::EndElement { .. } => {
+                if let Some((name, _)) = open.pop() {
+                    if name == "effect" {
+                        effect = None;
+                    }
+                }
+            }
+
+            _ => {}
+        }
+    }
+
+    Ok(bump_textures)
+}
+
+/// Loads every `<light>` in the document.
+fn load_lights(document: &Collada) -> Vec<scene::Light> {
+    let mut lights = Vec::new();
+    for library in document.libraries().filter_map(Library::as_library_lights) {
+        for light in library.lights() {
+            let (kind, color, attenuation) = match light.technique_common {
+                LightTechniqueCommon::Ambient(ref ambient) => {
+                    (LightKind::Ambient, ambient.color, None)
+                }
+
+                LightTechniqueCommon::Directional(ref directional) => {
+                    (LightKind::Directional, directional.color, None)
+                }
+
+                LightTechniqueCommon::Point(ref point) => {
+                    let attenuation = [
+                        point.constant_attenuation,
+                        point.linear_attenuation,
+                        point.quadratic_attenuation,
+                    ];
+                    (LightKind::Point, point.color, Some(attenuation))
+                }
+
+                LightTechniqueCommon::Spot(ref spot) => {
+                    let attenuation = [
+                        spot.constant_attenuation,
+                        spot.linear_attenuation,
+                        spot.quadratic_attenuation,
+                    ];
+                    let kind = LightKind::Spot {
+                        falloff_angle: spot.falloff_angle.unwrap_or(180.0),
+                        falloff_exponent: spot.falloff_exponent.unwrap_or(0.0),
+                    };
+                    (kind, spot.color, Some(attenuation))
+                }
+            };
+
+            // The COLLADA defaults are no attenuation at all: a constant factor of 1 and linear
+            // and quadratic factors of 0.
+            let attenuation = attenuation
+                .map(|[constant, linear, quadratic]| [
+                    constant.unwrap_or(1.0),
+                    linear.unwrap_or(0.0),
+                    quadratic.unwrap_or(0.0),
+                ])
+                .unwrap_or([1.0, 0.0, 0.0]);
+
+            lights.push(scene::Light {
+                id: light.id.clone(),
+                name: light.name.clone(),
+                kind,
+                color: Color::rgb(color[0], color[1], color[2]),
+                attenuation,
+            });
+        }
+    }
+
+    lights
+}
+
+/// Loads every `<camera>` in the document, converting distances into meters.
+fn load_cameras(document: &Collada, meters_per_unit: f32) -> Vec<scene::Camera> {
+    let mut cameras = Vec::new();
+    for library in document.libraries().filter_map(Library::as_library_cameras) {
+        for camera in library.cameras() {
+            let (projection, znear, zfar) = match camera.optics.technique_common {
+                Projection::Perspective(ref perspective) => (
+                    scene::Projection::Perspective {
+                        xfov: perspective.xfov,
+                        yfov: perspective.yfov,
+                        aspect_ratio: perspective.aspect_ratio,
+                    },
+                    perspective.znear,
+                    perspective.zfar,
+                ),
+
+                Projection::Orthographic(ref orthographic) => (
+                    scene::Projection::Orthographic {
+                        xmag: orthographic.xmag.map(|xmag| xmag * meters_per_unit),
+                        ymag: orthographic.ymag.map(|ymag| ymag * meters_per_unit),
+                        aspect_ratio: orthographic.aspect_ratio,
+                    },
+                    orthographic.znear,
+                    orthographic.zfar,
+                ),
+            };
+
+            cameras.push(scene::Camera {
+                id: camera.id.clone(),
+                name: camera.name.clone(),
+                projection,
+                znear: znear * meters_per_unit,
+                zfar: zfar * meters_per_unit,
+            });
+        }
+    }
+
+    cameras
+}
+
+/// Loads every `<morph>` controller in the document.
+///
+/// Each morph gets a copy of its base geometry's meshes, and its targets are stored as offsets
+/// from the base meshes. The geometries must already be converted into the viewer's coordinate
+/// system. Returns the morphs along with a map from the ID of each morph's weight source to the
+/// index of the morph, which animations use to target the weights.
+fn load_morphs(
+    document: &Collada,
+    geometries: &[Geometry],
+) -> Result<(Vec<scene::Morph>, HashMap<String, usize>), LoadError> {
+    let find_geometry = |id: &str| geometries.iter().find(|geometry| geometry.key() == Some(id));
+
+    let mut morphs = Vec::new();
+    let mut weight_sources = HashMap::new();
+    for library in document.libraries().filter_map(Library::as_library_controllers) {
+        for controller in library.controllers() {
+            let morph = match controller.control_element.as_morph() {
+                Some(morph) => morph,
+                None => { continue; }
+            };
+
+            let invalid = |reason| LoadError::InvalidMorph { controller: controller.id.clone(), reason };
+            let find_source = |semantic: &str| {
+                morph.targets.inputs.iter()
+                    .find(|input| input.semantic == semantic)
+                    .and_then(|input| morph.sources.iter().find(|source| source.id == input.source.id()))
+            };
+
+            let base = find_geometry(morph.source.id())
+                .ok_or_else(|| invalid("its base geometry doesn't exist"))?;
+
+            let target_ids = find_source("MORPH_TARGET")
+                .and_then(source_names)
+                .ok_or_else(|| invalid("it has no MORPH_TARGET source with an IDREF or Name array"))?;
+            let weight_source = find_source("MORPH_WEIGHT")
+                .ok_or_else(|| invalid("it has no MORPH_WEIGHT source"))?;
+            let weights = source_elements(weight_source, "MORPH_WEIGHT")?
+                .iter()
+                .map(|values| values.first().cloned().unwrap_or(0.0))
+                .collect::<Vec<_>>();
+            if weights.len() != target_ids.len() {
+                return Err(invalid("it has a different number of targets and weights"));
+            }
+
+            // Relative targets are already offsets, but normalized targets are complete shapes
+            // that the offsets have to be computed from.
+            let relative = match morph.method {
+                MorphMethod::Normalized => false,
+                MorphMethod::Relative => true,
+            };
+            let mut targets = Vec::with_capacity(target_ids.len());
+            for id in &target_ids {
+                let target = find_geometry(id).ok_or_else(|| invalid("one of its targets doesn't exist"))?;
+                targets.push(scene::MorphTarget {
+                    geometry: target.key().map(Into::into),
+                    deltas: morph_deltas(&base.meshes, &target.meshes, relative),
+                });
             }
 
-            let position = position.ok_or("Vertex missing position attribute")?;
-            builder.add_vertex(PolygonVertex { position, normal, texcoord });
-            let index = indices.len() as u32;
-            indices.push(index);
+            weight_sources.insert(weight_source.id.clone(), morphs.len());
+            morphs.push(scene::Morph {
+                id: controller.id.clone(),
+                name: controller.name.clone(),
+                meshes: base.meshes.clone(),
+                targets,
+                weights,
+            });
         }
     }
 
-    builder
-        .set_indices(&*indices)
-        .build()
-        .map_err(|_| "Failed to build mesh")
+    Ok((morphs, weight_sources))
+}
+
+/// Computes the offset of every vertex of the base meshes in a morph target.
+///
+/// Targets are normally exported with exactly the same faces as the base geometry, so their
+/// meshes line up vertex for vertex. If a target's meshes don't line up, vertices are matched by
+/// position index instead, and only the positions are offset.
+fn morph_deltas(base: &[MeshData], target: &[MeshData], relative: bool) -> Vec<Vec<scene::MorphDelta>> {
+    let difference = |target: Vector3, base: Vector3| {
+        if relative {
+            target
+        } else {
+            Vector3 { x: target.x - base.x, y: target.y - base.y, z: target.z - base.z }
+        }
+    };
+    let point_vector = |point: Point| Vector3 { x: point.x, y: point.y, z: point.z };
+    let zero = Vector3 { x: 0.0, y: 0.0, z: 0.0 };
+
+    let target_positions = target.iter()
+        .flat_map(|mesh| mesh.vertices.iter())
+        .filter_map(|vertex| vertex.position_index.map(|index| (index, vertex.position)))
+        .collect::<HashMap<_, _>>();
+
+    base.iter()
+        .enumerate()
+        .map(|(mesh_index, base_mesh)| {
+            let target_mesh = target.get(mesh_index)
+                .filter(|target_mesh| target_mesh.vertices.len() == base_mesh.vertices.len());
+
+            base_mesh.vertices.iter()
+                .enumerate()
+                .map(|(vertex_index, vertex)| match target_mesh {
+                    Some(target_mesh) => {
+                        let target_vertex = &target_mesh.vertices[vertex_index];
+                        scene::MorphDelta {
+                            position: difference(point_vector(target_vertex.position), point_vector(vertex.position)),
+                            normal: match (target_vertex.normal, vertex.normal) {
+                                (Some(target_normal), Some(normal)) => difference(target_normal, normal),
+                                _ => zero,
+                            },
+                        }
+                    }
+
+                    None => {
+                        let position = vertex.position_index
+                            .and_then(|index| target_positions.get(&index))
+                            .map(|&position| difference(point_vector(position), point_vector(vertex.position)))
+                            .unwrap_or(zero);
+                        scene::MorphDelta { position, normal: zero }
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Loads every `<skin>` controller in the document.
+///
+/// Each skin gets a copy of its source geometry's meshes with the joint weights filled in. The
+/// geometries must already be converted into the viewer's coordinate system by `basis`, and the
+/// skins' matrices are converted to match.
+///
+/// Skins whose source is a morph controller skin the morph's base meshes, since the viewer can't
+/// combine morphing and skinning.
+fn load_skins(
+    document: &Collada,
+    geometries: &[Geometry],
+    morphs: &[scene::Morph],
+    basis: Matrix,
+) -> Result<Vec<scene::Skin>, LoadError> {
+    // Conjugating by the basis makes the matrices operate on converted geometry, the same as
+    // node transforms.
+    let inverse_basis = basis.inverse().unwrap_or_else(Matrix::identity);
+    let convert = |matrix: Matrix| basis * matrix * inverse_basis;
+
+    let mut skins = Vec::new();
+    for library in document.libraries().filter_map(Library::as_library_controllers) {
+        for controller in library.controllers() {
+            let skin = match controller.control_element.as_skin() {
+                Some(skin) => skin,
+                None => { continue; }
+            };
+
+            let invalid = |reason| LoadError::InvalidSkin { controller: controller.id.clone(), reason };
+
+            let source_id = skin.source.id();
+            let source_meshes = match geometries.iter().find(|geometry| geometry.key() == Some(source_id)) {
+                Some(geometry) => &geometry.meshes,
+                None => {
+                    let morph = morphs.iter()
+                        .find(|morph| morph.key() == Some(source_id))
+                        .ok_or_else(|| invalid("its source geometry doesn't exist"))?;
+                    println!(
+                        "WARNING: Skin controller {:?} skins morph {:?}, the morph targets will be ignored",
+                        controller.id,
+                        source_id,
+                    );
+                    &morph.meshes
+                }
+            };
+
+            // The joints are listed by SID in a Name array, or by ID in an IDREF array.
+            let joint_input = skin.joints.inputs.iter()
+                .find(|input| input.semantic == "JOINT")
+                .ok_or_else(|| invalid("<joints> has no JOINT input"))?;
+            let joints = skin_source(skin, joint_input.source.id())
+                .and_then(source_names)
+                .ok_or_else(|| invalid("the JOINT source doesn't have a Name or IDREF array"))?;
+
+            let matrix_input = skin.joints.inputs.iter()
+                .find(|input| input.semantic == "INV_BIND_MATRIX")
+                .ok_or_else(|| invalid("<joints> has no INV_BIND_MATRIX input"))?;
+            let matrix_source = skin_source(skin, matrix_input.source.id())
+                .ok_or_else(|| invalid("the INV_BIND_MATRIX source doesn't exist"))?;
+            let inverse_bind_matrices = source_elements(matrix_source, "INV_BIND_MATRIX")?
+                .iter()
+                .map(|values| convert(Matrix::from_row_major(values)))
+                .collect::<Vec<_>>();
+            if inverse_bind_matrices.len() != joints.len() {
+                return Err(invalid("the number of inverse bind matrices doesn't match the number of joints"));
+            }
+
+            // `<vertex_weights>` lists the influences of each position in the source geometry,
+            // where each influence is a pair of a joint index and an index into the WEIGHT
+            // source.
+            let weights = &skin.vertex_weights;
+            let joint_offset = weights.inputs.iter()
+                .find(|input| input.semantic == "JOINT")
+                .ok_or_else(|| invalid("<vertex_weights> has no JOINT input"))?
+                .offset as usize;
+            let weight_input = weights.inputs.iter()
+                .find(|input| input.semantic == "WEIGHT")
+                .ok_or_else(|| invalid("<vertex_weights> has no WEIGHT input"))?;
+            let weight_source = skin_source(skin, weight_input.source.id())
+                .ok_or_else(|| invalid("the WEIGHT source doesn't exist"))?;
+
+            let stride = weights.inputs.iter().map(|input| input.offset as usize + 1).max().unwrap_or(0);
+            let mut pairs = weights.v.chunks(stride.max(1));
+            let mut influences = Vec::with_capacity(weights.vcount.len());
+            for &count in &weights.vcount {
+                let mut position_joints = Vec::with_capacity(count);
+                for _ in 0..count {
+                    let pair = pairs.next()
+                        .filter(|pair| pair.len() == stride)
+                        .ok_or_else(|| invalid("<v> has fewer influences than <vcount>"))?;
+                    let joint = pair[joint_offset];
+                    if joint >= joints.len() {
+                        return Err(invalid("an influence references a joint that doesn't exist"));
+                    }
+
+                    let weight = source_element(weight_source, "WEIGHT", pair[weight_input.offset as usize])?
+                        .first()
+                        .cloned()
+                        .ok_or_else(|| invalid("the WEIGHT source has empty elements"))?;
+                    position_joints.push((joint, weight));
+                }
+
+                // Exporters don't always normalize the weights, but skinning needs them to add
+                // up to one.
+                let total = position_joints.iter().map(|&(_, weight)| weight).sum::<f32>();
+                if total > 0.0 {
+                    for influence in &mut position_joints {
+                        influence.1 /= total;
+                    }
+                }
+
+                influences.push(position_joints);
+            }
+
+            let mut meshes = source_meshes.clone();
+            for vertex in meshes.iter_mut().flat_map(|mesh| mesh.vertices.iter_mut()) {
+                vertex.joints = vertex.position_index
+                    .and_then(|index| influences.get(index))
+                    .cloned()
+                    .ok_or_else(|| invalid("<vertex_weights> has fewer entries than the geometry has positions"))?;
+            }
+
+            let bind_shape_matrix = skin.bind_shape_matrix.as_ref()
+                .map(|values| convert(Matrix::from_row_major(&values[..])))
+                .unwrap_or_else(Matrix::identity);
+
+            skins.push(scene::Skin {
+                id: controller.id.clone(),
+                name: controller.name.clone(),
+                meshes,
+                bind_shape_matrix,
+                joints,
+                inverse_bind_matrices,
+            });
+        }
+    }
+
+    Ok(skins)
+}
+
+/// Returns the `<source>` of a skin identified by `id`.
+fn skin_source<'a>(skin: &'a Skin, id: &str) -> Option<&'a Source> {
+    skin.sources.iter().find(|source| source.id == id)
+}
+
+/// Resolves effect texture references into loaded textures, loading each image only once.
+struct TextureCache<'a> {
+    base: &'a Path,
+    images: &'a [&'a Image],
+    textures: Vec<Texture>,
+
+    /// Maps image IDs to indices in `textures`.
+    loaded: HashMap<String, usize>,
+}
+
+impl<'a> TextureCache<'a> {
+    /// Returns the index of the texture referenced by a color-or-texture parameter, loading the
+    /// texture if necessary.
+    ///
+    /// The `texture` attribute of a `<texture>` names a `<sampler2D>` param in the profile, which
+    /// in turn names a `<surface>` param that is initialized from an image. Some exporters skip
+    /// the params and reference the image directly, so image IDs are also accepted, both in
+    /// place of the sampler and in place of the surface (which is how converted COLLADA 1.5
+    /// documents reference their images).
+    fn resolve(&mut self, profile: &ProfileCommon, value: &Option<ColorOrTexture>) -> Option<usize> {
+        match *value {
+            Some(ColorOrTexture::Texture(ref texture)) => Some(self.resolve_name(profile, &texture.texture)),
+            _ => None,
+        }
+    }
+
+    /// Returns the index of the texture named by the `texture` attribute of a `<texture>`,
+    /// loading the texture if necessary.
+    fn resolve_name(&mut self, profile: &ProfileCommon, name: &str) -> usize {
+        let find_param = |sid: &str| profile.new_params.iter().find(|param| param.sid == sid);
+        let sampler_source = find_param(name)
+            .and_then(|param| param.as_sampler_2d())
+            .and_then(|sampler| sampler.source.as_ref())
+            .map(String::as_str);
+        let image_id = sampler_source
+            .and_then(|surface| find_param(surface))
+            .and_then(|param| param.as_surface())
+            .and_then(|surface| surface.init_from.as_ref())
+            .map(String::as_str)
+            .or(sampler_source)
+            .unwrap_or(name);
+
+        if let Some(&index) = self.loaded.get(image_id) {
+            return index;
+        }
+
+        let image = self.images.iter()
+            .find(|image| image.id.as_ref().map(String::as_str) == Some(image_id));
+        let texture = match image.and_then(|image| image.init_from.as_ref()) {
+            Some(uri) => Texture::load(&texture::resolve_uri(self.base, uri)),
+            None => {
+                println!("WARNING: Texture references missing image {:?}, using a placeholder", image_id);
+                Texture::checkerboard()
+            }
+        };
+
+        let index = self.textures.len();
+        self.textures.push(texture);
+        self.loaded.insert(image_id.into(), index);
+        index
+    }
+}
+
+/// Fills out a material description from the shader of a `<profile_COMMON>` technique.
+fn process_technique(technique: &Technique, material: &mut scene::Material) {
+    // All of the common shaders share the same set of parameters, with each shader adding more
+    // parameters on top of the previous one, so pull out the parameters that each shader has.
+    let (shading, emission, transparent) = match technique.shader {
+        Shader::Constant(ref constant) => (Shading::Constant, &constant.emission, &constant.transparent),
+        Shader::Lambert(ref lambert) => (Shading::Lambert, &lambert.emission, &lambert.transparent),
+        Shader::Phong(ref phong) => (Shading::Phong, &phong.emission, &phong.transparent),
+        Shader::Blinn(ref blinn) => (Shading::Blinn, &blinn.emission, &blinn.transparent),
+    };
+
+    let (ambient, diffuse) = match technique.shader {
+        Shader::Constant(..) => (&None, &None),
+        Shader::Lambert(ref lambert) => (&lambert.ambient, &lambert.diffuse),
+        Shader::Phong(ref phong) => (&phong.ambient, &phong.diffuse),
+        Shader::Blinn(ref blinn) => (&blinn.ambient, &blinn.diffuse),
+    };
+
+    let (specular, shininess) = match technique.shader {
+        Shader::Constant(..) | Shader::Lambert(..) => (&None, &None),
+        Shader::Phong(ref phong) => (&phong.specular, &phong.shininess),
+        Shader::Blinn(ref blinn) => (&blinn.specular, &blinn.shininess),
+    };
+
+    let transparency = match technique.shader {
+        Shader::Constant(ref constant) => &constant.transparency,
+        Shader::Lambert(ref lambert) => &lambert.transparency,
+        Shader::Phong(ref phong) => &phong.transparency,
+        Shader::Blinn(ref blinn) => &blinn.transparency,
+    };
+
+    material.shading = shading;
+    if let Some(color) = color_value(emission) { material.emission = color; }
+    if let Some(color) = color_value(ambient) { material.ambient = color; }
+    if let Some(color) = color_value(diffuse) { material.diffuse = color; }
+    if let Some(color) = color_value(specular) { material.specular = color; }
+    if let Some(shininess) = float_value(shininess) { material.shininess = shininess; }
+
+    // The opacity of the surface is the transparent color's alpha scaled by the transparency
+    // factor (for the default `A_ONE` opaque mode), so the transparency is the remainder.
+    let factor = float_value(transparency).unwrap_or(1.0);
+    let alpha = transparent.as_ref()
+        .and_then(|transparent| color_value(&transparent.color_or_texture))
+        .map(|color| color.a);
+    if let Some(alpha) = alpha {
+        material.transparency = 1.0 - alpha * factor;
+    } else if transparency.is_some() {
+        material.transparency = 1.0 - factor;
+    }
+}
+
+/// Returns the color of a common color-or-texture parameter, or `None` if the parameter is
+/// missing or isn't a plain color.
+fn color_value(value: &Option<ColorOrTexture>) -> Option<Color> {
+    match *value {
+        Some(ColorOrTexture::Color(ref color)) => {
+            Some(Color::new(color.data[0], color.data[1], color.data[2], color.data[3]))
+        }
+
+        _ => None,
+    }
+}
+
+/// Returns the value of a common float-or-param parameter, or `None` if the parameter is missing
+/// or references a param.
+fn float_value(value: &Option<FloatOrParam>) -> Option<f32> {
+    match *value {
+        Some(FloatOrParam::Float(ref float)) => Some(float.data),
+        _ => None,
+    }
+}
+
+/// Adds `node` and all of its descendants to `nodes`, keyed by ID.
+fn collect_nodes<'a>(node: &'a ColladaNode, nodes: &mut HashMap<&'a str, &'a ColladaNode>) {
+    if let Some(ref id) = node.id {
+        nodes.insert(id, node);
+    }
+
+    for child in &node.children {
+        collect_nodes(child, nodes);
+    }
+}
+
+/// Flattens a COLLADA node hierarchy into the scene's node list.
+struct NodeBuilder<'a, 'b> {
+    scene: &'b mut Scene,
+
+    /// Every node in the document that has an ID, used to resolve `<instance_node>` elements.
+    nodes: HashMap<&'a str, &'a ColladaNode>,
+
+    /// The IDs of the nodes currently being instanced, used to detect nodes that instance
+    /// themselves.
+    instancing: Vec<&'a str>,
+
+    /// The skin instances whose joints still need to be resolved, as the index of the node, the
+    /// index of the instance in the node's skin list, and the IDs of the instance's skeleton
+    /// root nodes.
+    skeletons: Vec<(usize, usize, Vec<&'a str>)>,
+}
+
+impl<'a, 'b> NodeBuilder<'a, 'b> {
+    fn add_node(&mut self, node: &'a ColladaNode, parent: Option<usize>) -> Result<(), LoadError> {
+        let index = self.scene.nodes.len();
+
+        let mut geometries = Vec::with_capacity(node.instance_geometry.len());
+        for instance in &node.instance_geometry {
+            let id = instance.url.id();
+            let geometry = self.scene.find_geometry(id)
+                .ok_or_else(|| LoadError::MissingGeometry { node: node.id.clone(), id: id.into() })?;
+
+            let materials = self.bind_materials(instance.bind_material.as_ref())?;
+            geometries.push(GeometryInstance { geometry, materials });
+        }
+
+        // Controllers are either skins or morphs.
+        let mut skins = Vec::new();
+        let mut morphs = Vec::new();
+        for instance in &node.instance_controller {
+            let id = instance.url.id();
+            let materials = self.bind_materials(instance.bind_material.as_ref())?;
+
+            if let Some(skin) = self.scene.find_skin(id) {
+                let roots = instance.skeleton.iter().map(|skeleton| skeleton.id()).collect();
+                self.skeletons.push((index, skins.len(), roots));
+                skins.push(scene::SkinInstance { skin, joints: Vec::new(), materials });
+            } else if let Some(morph) = self.scene.find_morph(id) {
+                morphs.push(scene::MorphInstance { morph, materials });
+            } else {
+                return Err(LoadError::MissingController { node: node.id.clone(), id: id.into() });
+            }
+        }
+
+        let mut lights = Vec::with_capacity(node.instance_light.len());
+        for instance in &node.instance_light {
+            let id = instance.url.id();
+            let light = self.scene.find_light(id)
+                .ok_or_else(|| LoadError::MissingLight { node: node.id.clone(), id: id.into() })?;
+            lights.push(light);
+        }
+
+        let mut cameras = Vec::with_capacity(node.instance_camera.len());
+        for instance in &node.instance_camera {
+            let id = instance.url.id();
+            let camera = self.scene.find_camera(id)
+                .ok_or_else(|| LoadError::MissingCamera { node: node.id.clone(), id: id.into() })?;
+            cameras.push(camera);
+        }
+
+        self.scene.nodes.push(scene::Node {
+            id: node.id.clone(),
+            name: node.name.clone(),
+            sid: node.sid.clone(),
+            parent,
+            transforms: node.transforms.iter().map(convert_transform).collect(),
+            geometries,
+            lights,
+            cameras,
+            skins,
+            morphs,
+        });
+
+        // Instanced nodes are copied into the hierarchy as children of the instancing node.
+        for instance in &node.instance_node {
+            let id = instance.url.id();
+            if self.instancing.contains(&id) {
+                return Err(LoadError::RecursiveNode { id: id.into() });
+            }
+
+            let instanced = *self.nodes.get(id)
+                .ok_or_else(|| LoadError::MissingNode { node: node.id.clone(), id: id.into() })?;
+
+            self.instancing.push(id);
+            self.add_node(instanced, Some(index))?;
+            self.instancing.pop();
+        }
+
+        for child in &node.children {
+            self.add_node(child, Some(index))?;
+        }
+
+        Ok(())
+    }
+
+    /// Binds each of the material symbols in a `<bind_material>` to a material in the document.
+    fn bind_materials(&self, bind_material: Option<&BindMaterial>) -> Result<HashMap<String, usize>, LoadError> {
+        let mut materials = HashMap::new();
+        let bindings = bind_material.iter()
+            .flat_map(|bind_material| bind_material.technique_common.instance_materials.iter());
+        for binding in bindings {
+            let id = binding.target.id();
+            let material = self.scene.find_material(id)
+                .ok_or_else(|| LoadError::MissingMaterial { id: id.into() })?;
+            materials.insert(binding.symbol.clone(), material);
+        }
+
+        Ok(materials)
+    }
+}
+
+/// Finds the node for each of a skin's joints.
+///
+/// Joints are matched against node SIDs within the hierarchies rooted at the instance's
+/// `<skeleton>` nodes, or anywhere in the scene if the instance has no skeleton. Skins that list
+/// their joints in an IDREF array reference nodes by ID instead, so IDs are matched as a
+/// fallback.
+fn resolve_joints(scene: &Scene, skin: usize, roots: &[&str]) -> Result<Vec<usize>, LoadError> {
+    let in_skeleton = |node: usize| -> bool {
+        if roots.is_empty() {
+            return true;
+        }
+
+        let mut current = Some(node);
+        while let Some(index) = current {
+            let id = scene.nodes[index].id.as_ref().map(String::as_str);
+            if id.map_or(false, |id| roots.contains(&id)) {
+                return true;
+            }
+
+            current = scene.nodes[index].parent;
+        }
+
+        false
+    };
+
+    let skin = &scene.skins[skin];
+    skin.joints.iter()
+        .map(|joint| {
+            let matches = |key: Option<&String>| key == Some(joint);
+            (0..scene.nodes.len())
+                .find(|&index| matches(scene.nodes[index].sid.as_ref()) && in_skeleton(index))
+                .or_else(|| (0..scene.nodes.len()).find(|&index| matches(scene.nodes[index].id.as_ref()) && in_skeleton(index)))
+                .ok_or_else(|| LoadError::MissingJoint { controller: skin.id.clone(), joint: joint.clone() })
+        })
+        .collect()
+}
+
+/// Loads the channels of every `<animation>` in the document.
+///
+/// Only channels that target the transforms of nodes in the scene or the weights of morphs are
+/// supported, and any other channels are skipped with a warning. A channel that targets a node
+/// that was instanced several times animates every copy of the node. `morph_weights` maps the
+/// IDs of the morphs' weight sources to the morphs' indices.
+fn load_animations(
+    document: &Collada,
+    scene: &Scene,
+    morph_weights: &HashMap<String, usize>,
+) -> Result<Vec<animation::Channel>, LoadError> {
+    let mut animations = Vec::new();
+    for library in document.libraries().filter_map(Library::as_library_animations) {
+        for animation in library.animations() {
+            collect_animations(animation, &mut animations);
+        }
+    }
+
+    // Channels and samplers usually reference samplers and sources in the same `<animation>`,
+    // but references to other animations are allowed too.
+    let sources = animations.iter()
+        .flat_map(|animation| animation.sources.iter())
+        .map(|source| (source.id.as_str(), source))
+        .collect::<HashMap<_, _>>();
+    let samplers = animations.iter()
+        .flat_map(|animation| animation.samplers.iter())
+        .filter_map(|sampler| sampler.id.as_ref().map(|id| (id.as_str(), sampler)))
+        .collect::<HashMap<_, _>>();
+
+    let mut channels = Vec::new();
+    for animation in &animations {
+        let invalid = |reason| LoadError::InvalidAnimation { animation: animation.id.clone(), reason };
+
+        for channel in &animation.channels {
+            let targets = channel_targets(scene, morph_weights, &channel.target);
+            if targets.is_empty() {
+                println!(
+                    "WARNING: Skipping animation channel targeting {:?}, only node transforms and morph weights can be animated",
+                    channel.target,
+                );
+                continue;
+            }
+
+            let sampler = samplers.get(channel.source.id())
+                .ok_or_else(|| invalid("a channel references a sampler that doesn't exist"))?;
+            let keys = sampler_keys(&animation.id, sampler, &sources)?;
+            for target in targets {
+                channels.push(animation::Channel { target, keys: keys.clone() });
+            }
+        }
+    }
+
+    Ok(channels)
+}
+
+/// Adds `animation` and all of its nested animations to `animations`.
+fn collect_animations<'a>(animation: &'a Animation, animations: &mut Vec<&'a Animation>) {
+    animations.push(animation);
+    for child in &animation.animations {
+        collect_animations(child, animations);
+    }
+}
+
+/// Finds the values targeted by a channel's target address, e.g. `Cube/rotateZ.ANGLE`.
+///
+/// Node transforms are addressed by the ID of a node, followed by the SID of one of the node's
+/// transforms, optionally followed by a member selection: a named component such as `.X`, or an
+/// array index such as `(3)` or, for matrices, a row and column such as `(0)(3)`.
+///
+/// Morph weights are addressed by the ID of the morph's weight source followed by the index of
+/// the weight, e.g. `Face-morph-weights(2)`. Some exporters address them through the morph
+/// controller's ID instead, e.g. `Face-morph/weights(2)`, so that's accepted too.
+///
+/// Returns an empty list if the address doesn't target anything that can be animated.
+fn channel_targets(scene: &Scene, morph_weights: &HashMap<String, usize>, address: &str) -> Vec<Target> {
+    // Addresses without a path can only be morph weights.
+    let mut parts = address.splitn(2, '/');
+    let (id, path) = match (parts.next(), parts.next()) {
+        (Some(id), Some(path)) => (id, path),
+        _ => {
+            let index_start = address.find('(').unwrap_or(address.len());
+            let (id, member) = address.split_at(index_start);
+            return match (morph_weights.get(id), array_index(member)) {
+                (Some(&morph), Some(target)) => vec![Target::MorphWeight { morph, target }],
+                _ => Vec::new(),
+            };
+        }
+    };
+
+    if let Some(morph) = scene.find_morph(id) {
+        let index_start = path.find('(').unwrap_or(path.len());
+        return match array_index(&path[index_start..]) {
+            Some(target) => vec![Target::MorphWeight { morph, target }],
+            None => Vec::new(),
+        };
+    }
+
+    let member_start = path.find(|c| c == '.' || c == '(').unwrap_or(path.len());
+    let (sid_path, member) = path.split_at(member_start);
+    let sid = sid_path.rsplit('/').next().unwrap_or(sid_path);
+
+    let component = match member {
+        "" => None,
+        ".X" => Some(0),
+        ".Y" => Some(1),
+        ".Z" => Some(2),
+        ".ANGLE" => Some(3),
+        _ => {
+            let indices = member.split(|c| c == '(' || c == ')')
+                .filter(|index| !index.is_empty())
+                .map(str::parse::<usize>)
+                .collect::<Result<Vec<_>, _>>();
+            match indices {
+                Ok(ref indices) if indices.len() == 1 => Some(indices[0]),
+                Ok(ref indices) if indices.len() == 2 => Some(indices[0] * 4 + indices[1]),
+                _ => { return Vec::new(); }
+            }
+        }
+    };
+
+    let mut targets = Vec::new();
+    for (node_index, node) in scene.nodes.iter().enumerate() {
+        if node.id.as_ref().map(String::as_str) != Some(id) {
+            continue;
+        }
+
+        let transform = node.transforms.iter()
+            .position(|transform| transform.sid.as_ref().map(String::as_str) == Some(sid));
+        if let Some(transform) = transform {
+            let value_count = node.transforms[transform].kind.value_count();
+            if component.map_or(true, |component| component < value_count) {
+                targets.push(Target::Transform { node: node_index, transform, component });
+            }
+        }
+    }
+
+    targets
+}
+
+/// Parses a single array index member selection, e.g. `(2)`.
+fn array_index(member: &str) -> Option<usize> {
+    if member.starts_with('(') && member.ends_with(')') {
+        member[1..member.len() - 1].parse().ok()
+    } else {
+        None
+    }
+}
+
+/// Reads the keyframes of an animation sampler.
+///
+/// Samplers without an `INTERPOLATION` input use linear interpolation. Interpolation types other
+/// than step, linear, and Bezier aren't supported, and fall back to linear interpolation with a
+/// warning.
+fn sampler_keys(
+    animation_id: &Option<String>,
+    sampler: &Sampler,
+    sources: &HashMap<&str, &Source>,
+) -> Result<Vec<animation::Key>, LoadError> {
+    let invalid = |reason| LoadError::InvalidAnimation { animation: animation_id.clone(), reason };
+    let input_source = |semantic: &str| {
+        sampler.inputs.iter()
+            .find(|input| input.semantic == semantic)
+            .and_then(|input| sources.get(input.source.id()))
+    };
+
+    let times = input_source("INPUT")
+        .ok_or_else(|| invalid("the sampler has no INPUT source"))
+        .and_then(|source| source_elements(source, "INPUT"))?;
+    let outputs = input_source("OUTPUT")
+        .ok_or_else(|| invalid("the sampler has no OUTPUT source"))
+        .and_then(|source| source_elements(source, "OUTPUT"))?;
+    if times.len() != outputs.len() {
+        return Err(invalid("the sampler's INPUT and OUTPUT sources have different numbers of values"));
+    }
+
+    let interpolations = match input_source("INTERPOLATION") {
+        Some(source) => {
+            let names = source_names(source)
+                .ok_or_else(|| invalid("the sampler's INTERPOLATION source doesn't have a Name array"))?;
+
+            let mut unsupported = false;
+            let interpolations = (0..times.len())
+                .map(|index| match names.get(index).map(String::as_str) {
+                    Some("STEP") => Interpolation::Step,
+                    Some("BEZIER") => Interpolation::Bezier,
+                    Some("LINEAR") | None => Interpolation::Linear,
+                    Some(_) => {
+                        unsupported = true;
+                        Interpolation::Linear
+                    }
+                })
+                .collect::<Vec<_>>();
+            if unsupported {
+                println!(
+                    "WARNING: Sampler {:?} uses an unsupported interpolation type, using linear interpolation instead",
+                    sampler.id,
+                );
+            }
+
+            interpolations
+        }
+
+        None => vec![Interpolation::Linear; times.len()],
+    };
+
+    let in_tangents = match input_source("IN_TANGENT") {
+        Some(source) => Some(source_elements(source, "IN_TANGENT")?),
+        None => None,
+    };
+    let out_tangents = match input_source("OUT_TANGENT") {
+        Some(source) => Some(source_elements(source, "OUT_TANGENT")?),
+        None => None,
+    };
+
+    // Tangents are usually 2D control points with a time and a value for each of the output's
+    // values, but 1D tangents with only a value are also allowed. Those are placed a third of
+    // the way to the neighboring keyframe, which makes them behave like Hermite tangents.
+    let time = |index: usize| times.get(index).and_then(|time| time.first()).cloned();
+    let control_points = |tangents: &Option<Vec<Vec<f32>>>, index: usize, width: usize, neighbor: Option<f32>| {
+        let key_time = time(index).unwrap_or(0.0);
+        match tangents.as_ref().and_then(|tangents| tangents.get(index)) {
+            Some(values) if values.len() == width * 2 => {
+                values.chunks(2).map(|pair| [pair[0], pair[1]]).collect()
+            }
+
+            Some(values) if values.len() == width => {
+                let control_time = key_time + (neighbor.unwrap_or(key_time) - key_time) / 3.0;
+                values.iter().map(|&value| [control_time, value]).collect()
+            }
+
+            _ => Vec::new(),
+        }
+    };
+
+    let mut keys = Vec::with_capacity(times.len());
+    for (index, values) in outputs.into_iter().enumerate() {
+        let width = values.len();
+        let previous = if index > 0 { time(index - 1) } else { None };
+        keys.push(animation::Key {
+            time: time(index).ok_or_else(|| invalid("the sampler's INPUT source has empty elements"))?,
+            in_tangents: control_points(&in_tangents, index, width, previous),
+            out_tangents: control_points(&out_tangents, index, width, time(index + 1)),
+            values,
+            interpolation: interpolations[index],
+        });
+    }
+
+    keys.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(::std::cmp::Ordering::Equal));
+    Ok(keys)
+}
+
+/// Converts a COLLADA transformation element into a scene transform.
+fn convert_transform(transform: &TransformationElement) -> Transform {
+    fn values<A: Default + AsMut<[f32]>>(data: &[f32]) -> A {
+        let mut values = A::default();
+        for (value, &datum) in values.as_mut().iter_mut().zip(data) {
+            *value = datum;
+        }
+
+        values
+    }
+
+    match *transform {
+        TransformationElement::LookAt(ref look_at) => Transform {
+            sid: look_at.sid.clone(),
+            kind: TransformKind::LookAt(values(&look_at.data[..])),
+        },
+
+        TransformationElement::Matrix(ref matrix) => Transform {
+            sid: matrix.sid.clone(),
+            kind: TransformKind::Matrix(Matrix::from_row_major(&matrix.data[..])),
+        },
+
+        TransformationElement::Rotate(ref rotate) => Transform {
+            sid: rotate.sid.clone(),
+            kind: TransformKind::Rotate(values(&rotate.data[..])),
+        },
+
+        TransformationElement::Scale(ref scale) => Transform {
+            sid: scale.sid.clone(),
+            kind: TransformKind::Scale(values(&scale.data[..])),
+        },
+
+        TransformationElement::Skew(ref skew) => Transform {
+            sid: skew.sid.clone(),
+            kind: TransformKind::Skew(values(&skew.data[..])),
+        },
+
+        TransformationElement::Translate(ref translate) => Transform {
+            sid: translate.sid.clone(),
+            kind: TransformKind::Translate(values(&translate.data[..])),
+        },
+    }
+}
+
+/// A single polygon from a COLLADA primitive.
+///
+/// Each corner is the slice of the primitive's `<p>` element that holds the indices for that
+/// corner, with one index for each input offset.
+struct Face<'a> {
+    corners: Vec<&'a [usize]>,
+    holes: Vec<Vec<&'a [usize]>>,
+}
+
+/// The faces of a COLLADA primitive, normalized so that every type of primitive can be converted
+/// into a mesh the same way.
+struct Faces<'a> {
+    inputs: &'a [SharedInput],
+    faces: Vec<Face<'a>>,
+
+    /// The material symbol of the primitive, which is bound to an actual material when the
+    /// geometry is instanced.
+    material: Option<&'a String>,
+}
+
+/// Converts a COLLADA primitive into a list of faces.
+///
+/// Returns `Ok(None)` for primitives that can't be converted into a polygon mesh, i.e. line
+/// primitives.
+fn primitive_faces<'a>(
+    geometry_id: Option<&String>,
+    primitive: &'a Primitive,
+) -> Result<Option<Faces<'a>>, LoadError> {
+    let (kind, inputs, material) = match *primitive {
+        Primitive::Lines(..) | Primitive::Linestrips(..) => {
+            println!(
+                "WARNING: Skipping line primitive in geometry {:?}, line meshes are not supported",
+                geometry_id,
+            );
+            return Ok(None);
+        }
+
+        Primitive::Polygons(ref polygons) => ("polygons", &*polygons.inputs, polygons.material.as_ref()),
+        Primitive::Polylist(ref polylist) => ("polylist", &*polylist.inputs, polylist.material.as_ref()),
+        Primitive::Triangles(ref triangles) => ("triangles", &*triangles.inputs, triangles.material.as_ref()),
+        Primitive::Trifans(ref trifans) => ("trifans", &*trifans.inputs, trifans.material.as_ref()),
+        Primitive::Tristrips(ref tristrips) => ("tristrips", &*tristrips.inputs, tristrips.material.as_ref()),
+    };
+
+    // Each corner has one index for each distinct input offset.
+    let stride = inputs.iter().map(|input| input.offset as usize + 1).max().unwrap_or(0);
+    if stride == 0 {
+        return Ok(None);
+    }
+
+    // Inputs with semantics that `process_corner` doesn't know are skipped for every corner, so
+    // they're reported once here rather than once per corner.
+    for input in inputs {
+        match input.semantic.as_ref() {
+            "VERTEX" | "NORMAL" | "TEXCOORD" | "COLOR" | "TANGENT" | "TEXTANGENT" | "BINORMAL" | "TEXBINORMAL" => {}
+            semantic @ _ => {
+                println!(
+                    "WARNING: Ignoring unknown semantic {:?} in {} primitive of geometry {:?}",
+                    semantic,
+                    kind,
+                    geometry_id,
+                );
+            }
+        }
+    }
+
+    let invalid = || LoadError::InvalidIndices { geometry: geometry_id.cloned(), primitive: kind };
+
+    // Splits a `<p>` element into the per-corner index slices.
+    let corners = |p: &'a [usize]| -> Result<Vec<&'a [usize]>, LoadError> {
+        if p.len() % stride != 0 {
+            return Err(invalid());
+        }
+
+        Ok(p.chunks(stride).collect())
+    };
+
+    let mut faces = Vec::new();
+    match *primitive {
+        Primitive::Polygons(ref polygons) => {
+            for p in &polygons.p {
+                faces.push(Face { corners: corners(p)?, holes: Vec::new() });
+            }
+
+            for ph in &polygons.ph {
+                let mut holes = Vec::with_capacity(ph.h.len());
+                for h in &ph.h {
+                    holes.push(corners(h)?);
+                }
+
+                faces.push(Face { corners: corners(&ph.p)?, holes });
+            }
+        }
+
+        Primitive::Polylist(ref polylist) => {
+            let mut corners = corners(polylist.p.as_ref().map(Vec::as_slice).unwrap_or(&[]))?;
+            let vcount = polylist.vcount.as_ref().map(Vec::as_slice).unwrap_or(&[]);
+            if vcount.iter().sum::<usize>() != corners.len() {
+                return Err(invalid());
+            }
+
+            for &count in vcount {
+                let rest = corners.split_off(count);
+                faces.push(Face { corners, holes: Vec::new() });
+                corners = rest;
+            }
+        }
+
+        Primitive::Triangles(ref triangles) => {
+            let corners = corners(triangles.p.as_ref().map(Vec::as_slice).unwrap_or(&[]))?;
+            if corners.len() % 3 != 0 {
+                return Err(invalid());
+            }
+
+            for triangle in corners.chunks(3) {
+                faces.push(Face { corners: triangle.to_vec(), holes: Vec::new() });
+            }
+        }
+
+        // Each `<p>` in a fan is a separate fan, where every triangle shares the first corner.
+        Primitive::Trifans(ref trifans) => {
+            for p in &trifans.p {
+                let corners = corners(p)?;
+                for index in 1..corners.len().saturating_sub(1) {
+                    faces.push(Face {
+                        corners: vec![corners[0], corners[index], corners[index + 1]],
+                        holes: Vec::new(),
+                    });
+                }
+            }
+        }
+
+        // Each `<p>` in a strip is a separate strip, where every triangle is made up of the
+        // previous two corners and the current one. Every other triangle has its first two
+        // corners swapped so that all triangles in the strip have the same winding.
+        Primitive::Tristrips(ref tristrips) => {
+            for p in &tristrips.p {
+                let corners = corners(p)?;
+                for index in 2..corners.len() {
+                    let triangle = if index % 2 == 0 {
+                        vec![corners[index - 2], corners[index - 1], corners[index]]
+                    } else {
+                        vec![corners[index - 1], corners[index - 2], corners[index]]
+                    };
+                    faces.push(Face { corners: triangle, holes: Vec::new() });
+                }
+            }
+        }
+
+        Primitive::Lines(..) | Primitive::Linestrips(..) => unreachable!(),
+    }
+
+    Ok(Some(Faces { inputs, faces, material }))
+}
+
+/// Builds a triangle mesh from the faces of a COLLADA primitive.
+///
+/// If `options.weld_vertices` is set, corners that have the same index for every input are
+/// known to have identical vertex data, so they share a single vertex in the output mesh.
+fn process_faces(
+    geometry_id: Option<&String>,
+    mesh: &ColladaMesh,
+    faces: &Faces,
+    options: &LoadOptions,
+) -> Result<MeshData, LoadError> {
+    let mut data = MeshData::default();
+    data.material = faces.material.cloned();
+    let mut welded = HashMap::<&[usize], usize>::new();
+
+    for face in &faces.faces {
+        // Find the vertex for each of the polygon's corners so that the polygon can be
+        // triangulated once all of its corners are known. The corners of any holes follow the
+        // corners of the outer boundary.
+        let mut vertex_indices = Vec::new();
+        for &corner in face.corners.iter().chain(face.holes.iter().flat_map(|hole| hole.iter())) {
+            if let Some(&index) = welded.get(corner) {
+                vertex_indices.push(index);
+                continue;
+            }
+
+            let index = data.vertices.len();
+            data.vertices.push(process_corner(geometry_id, mesh, faces.inputs, corner)?);
+            if options.weld_vertices {
+                welded.insert(corner, index);
+            }
+
+            vertex_indices.push(index);
+        }
+
+        // Triangulate the polygon and add its triangles to the mesh.
+        let positions = vertex_indices.iter()
+            .map(|&index| data.vertices[index].position)
+            .collect::<Vec<_>>();
+        let (outer, mut rest) = positions.split_at(face.corners.len());
+        let mut holes = Vec::with_capacity(face.holes.len());
+        for hole in &face.holes {
+            let (hole_positions, remaining) = rest.split_at(hole.len());
+            holes.push(hole_positions.to_vec());
+            rest = remaining;
+        }
+
+        for triangle in triangulate::triangulate_with_holes(outer, &holes) {
+            data.indices.extend(triangle.iter().map(|&corner| vertex_indices[corner] as u32));
+        }
+    }
+
+    Ok(data)
+}
+
+/// Reads the vertex data for a single corner of a face.
+fn process_corner(
+    geometry_id: Option<&String>,
+    mesh: &ColladaMesh,
+    inputs: &[SharedInput],
+    corner: &[usize],
+) -> Result<Vertex, LoadError> {
+    let mut position = None;
+    let mut position_index = None;
+    let mut normal = None;
+    let mut texcoords = Vec::new();
+    let mut color = None;
+    let mut tangent = None;
+    let mut binormal = None;
+
+    // For each of the inputs, grab the index for the input's offset and then grab the vertex
+    // data.
+    for input in inputs {
+        let index = corner[input.offset as usize];
+
+        // Handle the input based on its semantic.
+        match input.semantic.as_ref() {
+            // The "VERTEX" semantic means that this input indexes into all sources specified in
+            // the `vertices` member of the host mesh.
+            "VERTEX" => {
+                // We're assuming that the input refers to the mesh's `vertices` member. If that
+                // assumption is incorrect, we're going to produce the wrong mesh data.
+                if mesh.vertices.id != input.source.id() {
+                    return Err(LoadError::ForeignVertices {
+                        expected: mesh.vertices.id.clone(),
+                        found: input.source.id().into(),
+                    });
+                }
+
+                // Find the input that corresponds to the "POSITION" semantic. The COLLADA spec
+                // requires that there be one in a `<vertices>` element.
+                let input = mesh.vertices.inputs.iter()
+                    .find(|input| input.semantic == "POSITION")
+                    .ok_or_else(|| LoadError::MissingPositionInput {
+                        vertices: mesh.vertices.id.clone(),
+                    })?;
+
+                let (x, y, z) = read_element(mesh, input.source.id(), "POSITION", index)?.xyz()?;
+                position = Some(Point::new(x, y, z));
+                position_index = Some(index);
+            }
+
+            "NORMAL" => {
+                let (x, y, z) = read_element(mesh, input.source.id(), "NORMAL", index)?.xyz()?;
+                normal = Some(Vector3 { x, y, z });
+            }
+
+            // Texture coordinates use the S and T params, and may optionally have a P param
+            // for 3D textures, which we don't support. Inputs with a `set` attribute are sorted
+            // by set so that the vertex's texcoords are in set order.
+            "TEXCOORD" => {
+                let element = read_element(mesh, input.source.id(), "TEXCOORD", index)?;
+                let set = input.set.unwrap_or(0);
+                let texcoord = Vector2 { x: element.component("S")?, y: element.component("T")? };
+
+                let position = texcoords.iter()
+                    .position(|&(other_set, _)| other_set > set)
+                    .unwrap_or(texcoords.len());
+                texcoords.insert(position, (set, texcoord));
+            }
+
+            // Colors have R, G, and B params, and an optional A param. Colors without alpha are
+            // treated as opaque.
+            "COLOR" => {
+                let element = read_element(mesh, input.source.id(), "COLOR", index)?;
+                color = Some(Color::new(
+                    element.component("R")?,
+                    element.component("G")?,
+                    element.component("B")?,
+                    element.optional("A").unwrap_or(1.0),
+                ));
+            }
+
+            // Geometric tangents and binormals are only used if there are no texture-space ones,
+            // since the texture-space ones are the ones needed for normal mapping.
+            "TANGENT" | "TEXTANGENT" => {
+                let (x, y, z) = read_element(mesh, input.source.id(), &input.semantic, index)?.xyz()?;
+                if tangent.is_none() || input.semantic == "TEXTANGENT" {
+                    tangent = Some(Vector3 { x, y, z });
+                }
+            }
+
+            "BINORMAL" | "TEXBINORMAL" => {
+                let (x, y, z) = read_element(mesh, input.source.id(), &input.semantic, index)?.xyz()?;
+                if binormal.is_none() || input.semantic == "TEXBINORMAL" {
+                    binormal = Some(Vector3 { x, y, z });
+                }
+            }
+
+            // Ignore any unknown semantics, which `primitive_faces` has already warned about.
+            _ => {}
+        }
+    }
+
+    let position = position.ok_or_else(|| LoadError::MissingPosition {
+        geometry: geometry_id.cloned(),
+    })?;
+    let texcoord = texcoords.into_iter().map(|(_, texcoord)| texcoord).collect();
+
+    Ok(Vertex {
+        position,
+        normal,
+        texcoord,
+        color,
+        tangent,
+        binormal,
+        position_index,
+        joints: Vec::new(),
+    })
+}
+
+/// A single element read from a `<source>`, with each component paired with the name of the
+/// accessor param that it corresponds to.
+struct Element<'a> {
+    source_id: &'a str,
+    semantic: &'a str,
+    components: Vec<(Option<&'a str>, f32)>,
+}
+
+impl<'a> Element<'a> {
+    /// Returns the component for the param named `name`, if there is one.
+    ///
+    /// Some exporters don't name their params at all, so if none of the params are named the
+    /// components are assumed to be in the conventional order, e.g. X, Y, Z or S, T.
+    fn optional(&self, name: &str) -> Option<f32> {
+        let named = self.components.iter()
+            .find(|&&(param, _)| param == Some(name))
+            .map(|&(_, component)| component);
+        if named.is_some() || self.components.iter().any(|&(param, _)| param.is_some()) {
+            return named;
+        }
+
+        let position = match name {
+            "X" | "S" | "R" | "U" => 0,
+            "Y" | "T" | "G" | "V" => 1,
+            "Z" | "P" | "B" => 2,
+            "W" | "Q" | "A" => 3,
+            _ => { return None; }
+        };
+        self.components.get(position).map(|&(_, component)| component)
+    }
+
+    /// Returns the component for the param named `name`, or an error if the source's accessor
+    /// has no such param.
+    fn component(&self, name: &'static str) -> Result<f32, LoadError> {
+        self.optional(name).ok_or_else(|| LoadError::MissingComponent {
+            id: self.source_id.into(),
+            semantic: self.semantic.into(),
+            component: name,
+        })
+    }
+
+    /// Returns the X, Y, and Z components of the element.
+    fn xyz(&self) -> Result<(f32, f32, f32), LoadError> {
+        Ok((self.component("X")?, self.component("Y")?, self.component("Z")?))
+    }
+}
+
+/// Reads the element at `index` in the source identified by `source_id`.
+fn read_element<'a>(
+    mesh: &'a ColladaMesh,
+    source_id: &'a str,
+    semantic: &'a str,
+    index: usize,
+) -> Result<Element<'a>, LoadError> {
+    // Find the mesh source identified by the input's `source` within the parent `Mesh` object.
+    let source = mesh.find_source(source_id)
+        .ok_or_else(|| LoadError::MissingSource {
+            id: source_id.into(),
+            semantic: semantic.into(),
+        })?;
+
+    // Get the data for the current vertex, and pair each component with the name of its param
+    // so that the caller can pick out the components it needs. Components past the accessor's
+    // params are left unnamed.
+    let values = source_element(source, semantic, index)?;
+    let params = source.common_accessor().map(|accessor| &*accessor.params).unwrap_or(&[]);
+    let components = values.into_iter()
+        .enumerate()
+        .map(|(index, value)| {
+            let name = params.get(index).and_then(|param| param.name.as_ref()).map(String::as_str);
+            (name, value)
+        })
+        .collect();
+
+    Ok(Element { source_id, semantic, components })
+}
+
+/// Reads the values of the element at `index` in a source, following the offset, stride, and
+/// count of the source's accessor.
+///
+/// Vertex data is almost always stored in float arrays, but int and bool arrays are accepted
+/// too and converted into floats, with `true` converted to 1.
+fn source_element(source: &Source, semantic: &str, index: usize) -> Result<Vec<f32>, LoadError> {
+    let accessor = source.common_accessor()
+        .ok_or_else(|| LoadError::MissingAccessor {
+            id: source.id.clone(),
+            semantic: semantic.into(),
+        })?;
+    if index >= accessor.count {
+        return Err(LoadError::IndexOutOfRange {
+            id: source.id.clone(),
+            semantic: semantic.into(),
+            index,
+            count: accessor.count,
+        });
+    }
+
+    let start = accessor.offset + index * accessor.stride;
+    let end = start + accessor.stride;
+    let values = match source.array {
+        Some(ref array) => {
+            if let Some(array) = array.as_float_array() {
+                array.data.get(start..end).map(<[f32]>::to_vec)
+            } else if let Some(array) = array.as_int_array() {
+                array.data.get(start..end).map(|values| values.iter().map(|&value| value as f32).collect())
+            } else if let Some(array) = array.as_bool_array() {
+                array.data.get(start..end)
+                    .map(|values| values.iter().map(|&value| if value { 1.0 } else { 0.0 }).collect())
+            } else {
+                return Err(LoadError::UnsupportedArray { id: source.id.clone(), semantic: semantic.into() });
+            }
+        }
+
+        None => {
+            return Err(LoadError::UnsupportedArray { id: source.id.clone(), semantic: semantic.into() });
+        }
+    };
+
+    values.ok_or_else(|| LoadError::InvalidAccessor { id: source.id.clone(), semantic: semantic.into() })
+}
+
+/// Reads every element of a source, as given by the `count` of the source's accessor.
+fn source_elements(source: &Source, semantic: &str) -> Result<Vec<Vec<f32>>, LoadError> {
+    let count = source.common_accessor().map_or(0, |accessor| accessor.count);
+    (0..count).map(|index| source_element(source, semantic, index)).collect()
+}
+
+/// Reads the names in a source's Name or IDREF array, following the source's accessor if it has
+/// one. Returns `None` if the source has some other kind of array, or if its accessor reads past
+/// the end of the array.
+fn source_names(source: &Source) -> Option<Vec<String>> {
+    let array = source.array.as_ref()?;
+    let names = array.as_name_array()
+        .map(|array| &array.data)
+        .or_else(|| array.as_idref_array().map(|array| &array.data))?;
+
+    match source.common_accessor() {
+        Some(accessor) => {
+            (0..accessor.count)
+                .map(|index| names.get(accessor.offset + index * accessor.stride).cloned())
+                .collect()
+        }
+
+        None => Some(names.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("resources").join(name)
+    }
+
+    fn read_fixture(name: &str) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        File::open(fixture(name)).unwrap().read_to_end(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn document_version_reads_root_attribute() {
+        let version_1_4 = document_version(&read_fixture("textured_quad_1_4.dae")).unwrap();
+        let version_1_5 = document_version(&read_fixture("textured_quad_1_5.dae")).unwrap();
+        assert_eq!(version_1_4.as_ref().map(String::as_str), Some("1.4.1"));
+        assert_eq!(version_1_5.as_ref().map(String::as_str), Some("1.5.0"));
+    }
+
+    #[test]
+    fn downgrade_1_5_rewrites_images() {
+        let output = String::from_utf8(downgrade_1_5(&read_fixture("textured_quad_1_5.dae")).unwrap()).unwrap();
+        assert!(output.contains("version=\"1.4.1\""));
+        assert!(output.contains("<init_from>checker.png</init_from>"));
+        assert!(!output.contains("<ref>"));
+        assert!(!output.contains("instance_image"));
+        assert!(output.contains("<source>checker-image</source>"));
+        assert!(!output.contains("author_website"));
+    }
+
+    #[test]
+    fn bump_textures_reads_extra_techniques() {
+        let document = br##"<COLLADA version="1.4.1">
+            <library_effects>
+                <effect id="fcollada">
+                    <profile_COMMON>
+                        <technique sid="common">
+                            <extra>
+                                <technique profile="FCOLLADA">
+                                    <bump><texture texture="fcollada-normal" texcoord="UVMap"/></bump>
+                                </technique>
+                            </extra>
+                        </technique>
+                    </profile_COMMON>
+                </effect>
+                <effect id="max">
+                    <extra>
+                        <technique profile="OpenCOLLADA3dsMax">
+                            <bump bumptype="NORMALMAP"><texture texture="max-normal" texcoord="CHANNEL1"/></bump>
+                        </technique>
+                    </extra>
+                </effect>
+                <effect id="unknown">
+                    <extra>
+                        <technique profile="SOMETHING_ELSE">
+                            <bump><texture texture="unknown-normal" texcoord="UVMap"/></bump>
+                        </technique>
+                    </extra>
+                </effect>
+            </library_effects>
+        </COLLADA>"##;
+
+        let bump_textures = bump_textures(document).unwrap();
+        assert_eq!(bump_textures.len(), 2);
+        assert_eq!(bump_textures.get("fcollada").map(String::as_str), Some("fcollada-normal"));
+        assert_eq!(bump_textures.get("max").map(String::as_str), Some("max-normal"));
+    }
+
+    #[test]
+    fn load_scene_1_5_matches_1_4() {
+        let options = LoadOptions::default();
+        let scene_1_4 = load_scene(fixture("textured_quad_1_4.dae"), &options).unwrap();
+        let scene_1_5 = load_scene(fixture("textured_quad_1_5.dae"), &options).unwrap();
+
+        // The scene types don't implement `PartialEq`, so they're compared by their debug output.
+        assert_eq!(format!("{:?}", scene_1_4.geometries), format!("{:?}", scene_1_5.geometries));
+        assert_eq!(format!("{:?}", scene_1_4.materials), format!("{:?}", scene_1_5.materials));
+        assert_eq!(format!("{:?}", scene_1_4.textures), format!("{:?}", scene_1_5.textures));
+
+        assert_eq!(scene_1_5.materials.len(), 1);
+        assert_eq!(scene_1_5.materials[0].diffuse_texture, Some(0));
+        assert_eq!(scene_1_5.textures.len(), 1);
+        let texture_name = scene_1_5.textures[0].path.as_ref().and_then(|path| path.file_name());
+        assert_eq!(texture_name.and_then(|name| name.to_str()), Some("checker.png"));
+    }
 }
@@ -0,0 +1,1050 @@
+//! Loading glTF 2.0 files, both as `.gltf` JSON with external or embedded buffers and as binary
+//! `.glb` files.
+//!
+//! Every glTF mesh is loaded as a geometry with one mesh per primitive, and the node hierarchy of
+//! the default scene is loaded as the scene's nodes. Materials use the PBR metallic-roughness
+//! model, which is approximated with the viewer's Phong materials. Only local files and data URIs
+//! are loaded; URIs that would need a network request are rejected.
+
+use base64;
+use loader::Loader;
+use matrix::Matrix;
+use mesh::{normalize, MeshData, Vertex};
+use normals;
+use polygon::math::{Color, Point, Vector2, Vector3};
+use scene::{self, Geometry, GeometryInstance, LoadOptions, Node, Scene, Shading, Transform, TransformKind, UpAxis};
+use serde_json::{self, Value};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use texture::{self, Texture};
+
+/// An error that occurred while loading a glTF file.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The file, or a buffer it references, couldn't be opened or read.
+    Io(io::Error),
+
+    /// The file's JSON couldn't be parsed.
+    Json(serde_json::Error),
+
+    /// The file is a binary glTF file with a malformed header or chunks.
+    InvalidGlb(&'static str),
+
+    /// The file's `asset.version` isn't a 2.x version.
+    UnsupportedVersion {
+        version: String,
+    },
+
+    /// The file requires an extension that isn't supported.
+    UnsupportedExtension {
+        extension: String,
+    },
+
+    /// A buffer or image has a URI that can't be loaded from the local file system, e.g. an
+    /// `http://` URI.
+    UnsupportedUri {
+        uri: String,
+    },
+
+    /// A data URI isn't valid base64-encoded data.
+    InvalidDataUri {
+        uri: String,
+    },
+
+    /// An object in the file is malformed, e.g. an accessor that reads past the end of its
+    /// buffer view or a node that references a mesh that doesn't exist. `object` names the
+    /// object, e.g. "accessor 3".
+    InvalidObject {
+        object: String,
+        reason: &'static str,
+    },
+
+    /// The file doesn't contain any meshes that could be loaded.
+    NoMeshes,
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Io(ref error) => write!(f, "Failed to read file: {}", error),
+            LoadError::Json(ref error) => write!(f, "Failed to parse glTF JSON: {}", error),
+            LoadError::InvalidGlb(reason) => write!(f, "Invalid binary glTF file: {}", reason),
+
+            LoadError::UnsupportedVersion { ref version } => write!(
+                f,
+                "Unsupported glTF version {:?}, only 2.x is supported",
+                version,
+            ),
+
+            LoadError::UnsupportedExtension { ref extension } => write!(
+                f,
+                "The file requires the unsupported extension {:?}",
+                extension,
+            ),
+
+            LoadError::UnsupportedUri { ref uri } => write!(
+                f,
+                "Can't load {:?}, only local files and data URIs are supported",
+                uri,
+            ),
+
+            LoadError::InvalidDataUri { ref uri } => write!(f, "Invalid data URI {:?}", uri),
+
+            LoadError::InvalidObject { ref object, reason } => write!(
+                f,
+                "Invalid {} because {}",
+                object,
+                reason,
+            ),
+
+            LoadError::NoMeshes => write!(f, "No meshes found in the file"),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn description(&self) -> &str {
+        match *self {
+            LoadError::Io(..) => "Failed to read file",
+            LoadError::Json(..) => "Failed to parse glTF JSON",
+            LoadError::InvalidGlb(..) => "Invalid binary glTF file",
+            LoadError::UnsupportedVersion { .. } => "Unsupported glTF version",
+            LoadError::UnsupportedExtension { .. } => "File requires an unsupported extension",
+            LoadError::UnsupportedUri { .. } => "URI can't be loaded from the local file system",
+            LoadError::InvalidDataUri { .. } => "Invalid data URI",
+            LoadError::InvalidObject { .. } => "Object is malformed",
+            LoadError::NoMeshes => "No meshes found in the file",
+        }
+    }
+
+    fn cause(&self) -> Option<&dyn Error> {
+        match *self {
+            LoadError::Io(ref error) => Some(error),
+            LoadError::Json(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(from: io::Error) -> LoadError {
+        LoadError::Io(from)
+    }
+}
+
+impl From<serde_json::Error> for LoadError {
+    fn from(from: serde_json::Error) -> LoadError {
+        LoadError::Json(from)
+    }
+}
+
+/// The magic number at the start of a binary glTF file, "glTF" in ASCII.
+const GLB_MAGIC: u32 = 0x4654_6C67;
+
+const GLB_CHUNK_JSON: u32 = 0x4E4F_534A;
+const GLB_CHUNK_BIN: u32 = 0x004E_4942;
+
+/// Extensions that can be required by a file without affecting how it's loaded.
+const SUPPORTED_EXTENSIONS: &[&str] = &["KHR_materials_unlit"];
+
+/// The glTF loader, for the loader registry.
+pub struct GltfLoader;
+
+impl Loader for GltfLoader {
+    fn name(&self) -> &'static str {
+        "gltf"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["gltf", "glb"]
+    }
+
+    fn detect(&self, header: &[u8]) -> bool {
+        // Only binary files have a header. JSON files are picked by their extension.
+        read_u32(header, 0) == Some(GLB_MAGIC)
+    }
+
+    fn load(&self, path: &Path, options: &LoadOptions) -> Result<Scene, Box<dyn Error>> {
+        load_scene(path, options).map_err(Into::into)
+    }
+}
+
+/// Loads the glTF file at `path` as a scene.
+///
+/// Both `.gltf` and `.glb` files are supported; binary files are recognized by their header
+/// rather than their extension.
+pub fn load_scene<P: AsRef<Path>>(path: P, options: &LoadOptions) -> Result<Scene, LoadError> {
+    let path = path.as_ref();
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+
+    let (json, bin) = if read_u32(&bytes, 0) == Some(GLB_MAGIC) {
+        read_glb(&bytes)?
+    } else {
+        (&*bytes, None)
+    };
+    let json = serde_json::from_slice::<Value>(json)?;
+
+    let version = json.get("asset").and_then(|asset| string(asset, "version")).unwrap_or("");
+    if !version.starts_with("2.") {
+        return Err(LoadError::UnsupportedVersion { version: version.into() });
+    }
+
+    for extension in array(&json, "extensionsRequired").iter().filter_map(Value::as_str) {
+        if !SUPPORTED_EXTENSIONS.contains(&extension) {
+            return Err(LoadError::UnsupportedExtension { extension: extension.into() });
+        }
+    }
+
+    // Buffers are referenced relative to the file.
+    let base = path.parent().unwrap_or(Path::new(""));
+    let buffers = load_buffers(&json, base, bin)?;
+    let document = Document { json: &json, base, buffers };
+
+    let mut scene = Scene::default();
+    scene.basis = options.up_axis.unwrap_or(UpAxis::Y).basis(options.meters_per_unit.unwrap_or(1.0));
+
+    for (index, mesh) in array(&json, "meshes").iter().enumerate() {
+        let mut geometry = document.load_mesh(index, mesh, options)?;
+        for mesh in &mut geometry.meshes {
+            mesh.transform(&scene.basis);
+        }
+        scene.geometries.push(geometry);
+    }
+    if scene.geometries.iter().all(|geometry| geometry.meshes.is_empty()) {
+        return Err(LoadError::NoMeshes);
+    }
+
+    let (materials, textures) = document.load_materials()?;
+    scene.materials = materials;
+    scene.textures = textures;
+    scene.cameras = load_cameras(&json);
+    scene.nodes = load_nodes(&json, &scene)?;
+
+    if !array(&json, "skins").is_empty() {
+        println!("WARNING: glTF skins aren't supported, skinned meshes are shown in their bind pose");
+    }
+    if !array(&json, "animations").is_empty() {
+        println!("WARNING: glTF animations aren't supported, ignoring them");
+    }
+
+    Ok(scene)
+}
+
+/// Splits a binary glTF file into its JSON chunk and its optional binary chunk.
+fn read_glb(bytes: &[u8]) -> Result<(&[u8], Option<Vec<u8>>), LoadError> {
+    let version = read_u32(bytes, 4).ok_or(LoadError::InvalidGlb("the header is truncated"))?;
+    if version != 2 {
+        return Err(LoadError::UnsupportedVersion { version: version.to_string() });
+    }
+
+    let length = read_u32(bytes, 8).ok_or(LoadError::InvalidGlb("the header is truncated"))? as usize;
+    let bytes = bytes.get(..length).ok_or(LoadError::InvalidGlb("the file is shorter than its header says"))?;
+
+    let mut json = None;
+    let mut bin = None;
+    let mut offset = 12;
+    while offset < bytes.len() {
+        let chunk_length = read_u32(bytes, offset).ok_or(LoadError::InvalidGlb("a chunk header is truncated"))?;
+        let chunk_type = read_u32(bytes, offset + 4).ok_or(LoadError::InvalidGlb("a chunk header is truncated"))?;
+        let start = offset + 8;
+        let end = start.checked_add(chunk_length as usize)
+            .ok_or(LoadError::InvalidGlb("a chunk is longer than the file"))?;
+        let data = bytes.get(start..end).ok_or(LoadError::InvalidGlb("a chunk is longer than the file"))?;
+
+        // The JSON chunk always comes first, and unknown chunks are meant to be ignored.
+        match chunk_type {
+            GLB_CHUNK_JSON if json.is_none() => { json = Some(data); }
+            GLB_CHUNK_BIN if json.is_some() && bin.is_none() => { bin = Some(data.to_vec()); }
+            _ => {}
+        }
+
+        offset = end;
+    }
+
+    let json = json.ok_or(LoadError::InvalidGlb("the file has no JSON chunk"))?;
+    Ok((json, bin))
+}
+
+/// Returns the end of `count` elements of `element_size` bytes, placed `stride` bytes apart
+/// starting at `offset`, or `None` if the end doesn't fit in a `usize`.
+fn data_extent(offset: usize, count: usize, stride: usize, element_size: usize) -> Option<usize> {
+    if count == 0 {
+        return Some(offset);
+    }
+
+    (count - 1).checked_mul(stride)?.checked_add(element_size)?.checked_add(offset)
+}
+
+/// Reads a little-endian `u32` at `offset`, or returns `None` if it's past the end of `bytes`.
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let bytes = bytes.get(offset..offset + 4)?;
+    Some(bytes[0] as u32 | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16 | (bytes[3] as u32) << 24)
+}
+
+/// Loads every buffer in the file. A buffer without a URI refers to the binary chunk of a `.glb`
+/// file.
+fn load_buffers(json: &Value, base: &Path, mut bin: Option<Vec<u8>>) -> Result<Vec<Vec<u8>>, LoadError> {
+    let mut buffers = Vec::new();
+    for (index, buffer) in array(json, "buffers").iter().enumerate() {
+        let invalid = |reason| LoadError::InvalidObject { object: format!("buffer {}", index), reason };
+
+        let data = match string(buffer, "uri") {
+            Some(uri) => read_uri(base, uri)?,
+            None => bin.take().ok_or_else(|| invalid("it has no URI and the file has no binary chunk"))?,
+        };
+
+        let length = usize_property(buffer, "byteLength").ok_or_else(|| invalid("it has no byteLength"))?;
+        if data.len() < length {
+            return Err(invalid("its data is shorter than its byteLength"));
+        }
+
+        buffers.push(data);
+    }
+
+    Ok(buffers)
+}
+
+/// Reads the data referenced by a URI, which is either a base64 data URI or a path relative to
+/// `base`.
+fn read_uri(base: &Path, uri: &str) -> Result<Vec<u8>, LoadError> {
+    if uri.starts_with("data:") {
+        // Data URIs can be huge, so only the start of the URI is kept for the error message.
+        let invalid = || LoadError::InvalidDataUri { uri: uri.chars().take(40).collect() };
+        let comma = uri.find(',').ok_or_else(&invalid)?;
+        if !uri[..comma].ends_with(";base64") {
+            return Err(invalid());
+        }
+
+        return base64::decode(&uri[comma + 1..]).map_err(|_| invalid());
+    }
+
+    if uri.contains("://") && !uri.starts_with("file:") {
+        return Err(LoadError::UnsupportedUri { uri: uri.into() });
+    }
+
+    let mut data = Vec::new();
+    File::open(texture::resolve_uri(base, uri))?.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// A glTF file along with the data of its buffers.
+struct Document<'a> {
+    json: &'a Value,
+    base: &'a Path,
+    buffers: Vec<Vec<u8>>,
+}
+
+impl<'a> Document<'a> {
+    /// Loads a glTF mesh as a geometry, with one mesh for each of its primitives.
+    ///
+    /// The meshes' material symbols are the indices of their materials.
+    fn load_mesh(&self, index: usize, mesh: &Value, options: &LoadOptions) -> Result<Geometry, LoadError> {
+        let mut meshes = Vec::new();
+        for (primitive_index, primitive) in array(mesh, "primitives").iter().enumerate() {
+            let object = format!("mesh {} primitive {}", index, primitive_index);
+            let invalid = |reason| LoadError::InvalidObject { object: object.clone(), reason };
+
+            // Points and lines have no surface to shade.
+            let mode = usize_property(primitive, "mode").unwrap_or(4);
+            if mode < 4 {
+                println!("WARNING: Skipping {}, point and line primitives aren't supported", object);
+                continue;
+            }
+
+            if !array(primitive, "targets").is_empty() {
+                println!("WARNING: Morph targets of {} aren't supported, ignoring them", object);
+            }
+
+            let attributes = primitive.get("attributes").ok_or_else(|| invalid("it has no attributes"))?;
+
+            // Reads an attribute, checking that its accessor has one of the types that the
+            // attribute allows so that every element has the components it's indexed with below.
+            let attribute = |name: &str, types: &[&str]| -> Result<Option<Vec<Vec<f64>>>, LoadError> {
+                let accessor = match usize_property(attributes, name) {
+                    Some(accessor) => accessor,
+                    None => { return Ok(None); }
+                };
+
+                let accessor_type = array(self.json, "accessors").get(accessor).and_then(|accessor| string(accessor, "type"));
+                if !accessor_type.map_or(false, |accessor_type| types.iter().any(|&allowed| allowed == accessor_type)) {
+                    return Err(LoadError::InvalidObject {
+                        object: format!("{} {} attribute", object, name),
+                        reason: "its accessor has the wrong type",
+                    });
+                }
+
+                Ok(Some(self.read_accessor(accessor)?))
+            };
+
+            let positions = attribute("POSITION", &["VEC3"])?.ok_or_else(|| invalid("it has no POSITION attribute"))?;
+            let mut vertices = positions.iter()
+                .enumerate()
+                .map(|(position_index, position)| {
+                    let mut vertex = Vertex::new(Point::new(
+                        position[0] as f32,
+                        position[1] as f32,
+                        position[2] as f32,
+                    ));
+                    vertex.position_index = Some(position_index);
+                    vertex
+                })
+                .collect::<Vec<_>>();
+
+            let normals = attribute("NORMAL", &["VEC3"])?;
+            let tangents = attribute("TANGENT", &["VEC4"])?;
+            let colors = attribute("COLOR_0", &["VEC3", "VEC4"])?;
+            let mut texcoord_sets = Vec::new();
+            while let Some(texcoords) = attribute(&format!("TEXCOORD_{}", texcoord_sets.len()), &["VEC2"])? {
+                texcoord_sets.push(texcoords);
+            }
+
+            // Every attribute must have one element per vertex.
+            for elements in normals.iter().chain(&tangents).chain(&colors).chain(&texcoord_sets) {
+                if elements.len() != vertices.len() {
+                    return Err(invalid("its attributes have different numbers of elements"));
+                }
+            }
+
+            for (vertex_index, vertex) in vertices.iter_mut().enumerate() {
+                let vector = |values: &[f64]| Vector3 { x: values[0] as f32, y: values[1] as f32, z: values[2] as f32 };
+
+                if let Some(ref normals) = normals {
+                    vertex.normal = Some(vector(&normals[vertex_index]));
+                }
+
+                // The W component of a tangent gives the handedness of the tangent frame, which
+                // is what determines the direction of the binormal.
+                if let Some(ref tangents) = tangents {
+                    let tangent = vector(&tangents[vertex_index]);
+                    let sign = tangents[vertex_index][3] as f32;
+                    vertex.tangent = Some(tangent);
+                    vertex.binormal = vertex.normal.map(|normal| {
+                        let binormal = Vector3 {
+                            x: normal.y * tangent.z - normal.z * tangent.y,
+                            y: normal.z * tangent.x - normal.x * tangent.z,
+                            z: normal.x * tangent.y - normal.y * tangent.x,
+                        };
+                        normalize(Vector3 { x: binormal.x * sign, y: binormal.y * sign, z: binormal.z * sign })
+                    });
+                }
+
+                if let Some(ref colors) = colors {
+                    let color = &colors[vertex_index];
+                    let alpha = color.get(3).cloned().unwrap_or(1.0);
+                    vertex.color = Some(Color::new(color[0] as f32, color[1] as f32, color[2] as f32, alpha as f32));
+                }
+
+                // glTF texture coordinates start at the top left of the image, where the viewer's
+                // start at the bottom left.
+                for texcoords in &texcoord_sets {
+                    let texcoord = &texcoords[vertex_index];
+                    vertex.texcoord.push(Vector2 { x: texcoord[0] as f32, y: 1.0 - texcoord[1] as f32 });
+                }
+            }
+
+            let corners = match usize_property(primitive, "indices") {
+                Some(accessor) => {
+                    let mut corners = Vec::new();
+                    for element in self.read_accessor(accessor)? {
+                        let corner = element[0] as usize;
+                        if corner >= vertices.len() {
+                            return Err(invalid("one of its indices is out of range"));
+                        }
+                        corners.push(corner as u32);
+                    }
+                    corners
+                }
+
+                None => (0..vertices.len() as u32).collect(),
+            };
+
+            let indices = match mode {
+                // Every triangle in a strip is made up of the previous two corners and the
+                // current one, with every other triangle flipped so that they all have the same
+                // winding.
+                5 => (2..corners.len())
+                    .flat_map(|index| if index % 2 == 0 {
+                        vec![corners[index - 2], corners[index - 1], corners[index]]
+                    } else {
+                        vec![corners[index - 1], corners[index - 2], corners[index]]
+                    })
+                    .collect(),
+
+                // Every triangle in a fan shares the first corner.
+                6 => (2..corners.len())
+                    .flat_map(|index| vec![corners[0], corners[index - 1], corners[index]])
+                    .collect(),
+
+                _ => {
+                    if corners.len() % 3 != 0 {
+                        return Err(invalid("its number of indices isn't a multiple of three"));
+                    }
+                    corners
+                }
+            };
+
+            let mut data = MeshData {
+                vertices,
+                indices,
+                material: usize_property(primitive, "material").map(|material| material.to_string()),
+            };
+            if options.regenerate_normals || data.vertices.iter().any(|vertex| vertex.normal.is_none()) {
+                normals::generate(&mut data, options.normal_mode, options.crease_angle);
+            }
+
+            meshes.push(data);
+        }
+
+        Ok(Geometry {
+            id: None,
+            name: string(mesh, "name").map(String::from),
+            meshes,
+        })
+    }
+
+    /// Reads every element of an accessor, with each component converted to an `f64`.
+    ///
+    /// Normalized integer components are converted into the [0, 1] or [-1, 1] range. Accessors
+    /// without a buffer view start out as zeros, and sparse accessors have their sparse values
+    /// substituted in.
+    fn read_accessor(&self, index: usize) -> Result<Vec<Vec<f64>>, LoadError> {
+        let object = format!("accessor {}", index);
+        let invalid = |reason| LoadError::InvalidObject { object: object.clone(), reason };
+        let accessor = array(self.json, "accessors").get(index).ok_or_else(|| invalid("it doesn't exist"))?;
+
+        let count = usize_property(accessor, "count").ok_or_else(|| invalid("it has no count"))?;
+        let component_type = usize_property(accessor, "componentType").ok_or_else(|| invalid("it has no componentType"))?;
+        let component_size = component_size(component_type).ok_or_else(|| invalid("it has an unknown componentType"))?;
+        let normalized = accessor.get("normalized").and_then(Value::as_bool).unwrap_or(false);
+        let (columns, rows) = match string(accessor, "type") {
+            Some("SCALAR") => (1, 1),
+            Some("VEC2") => (1, 2),
+            Some("VEC3") => (1, 3),
+            Some("VEC4") => (1, 4),
+            Some("MAT2") => (2, 2),
+            Some("MAT3") => (3, 3),
+            Some("MAT4") => (4, 4),
+            _ => { return Err(invalid("it has an unknown type")); }
+        };
+
+        // Each column of a matrix starts on a 4-byte boundary, which only makes a difference for
+        // matrices with 1- or 2-byte components.
+        let column_stride = if columns > 1 { (rows * component_size + 3) / 4 * 4 } else { rows * component_size };
+        let element_size = column_stride * columns;
+
+        // Reads `count` tightly packed or strided elements starting at `offset` in `data` into
+        // `elements`.
+        let read_elements = |data: &[u8], offset: usize, stride: usize, elements: &mut [Vec<f64>]| -> Result<(), LoadError> {
+            for (element_index, element) in elements.iter_mut().enumerate() {
+                let start = offset + element_index * stride;
+                for column in 0..columns {
+                    for row in 0..rows {
+                        let at = start + column * column_stride + row * component_size;
+                        let bytes = data.get(at..at + component_size)
+                            .ok_or_else(|| invalid("it reads past the end of its buffer view"))?;
+                        element[column * rows + row] = read_component(bytes, component_type, normalized);
+                    }
+                }
+            }
+
+            Ok(())
+        };
+
+        // The count is checked against the data before anything is allocated, so that a bogus
+        // count produces an error rather than an enormous allocation. Accessors without a buffer
+        // view have no data to check against, but they always accompany attributes that do, so
+        // their count can't reasonably be larger than the largest buffer.
+        let view = match usize_property(accessor, "bufferView") {
+            Some(view) => {
+                let (data, stride) = self.buffer_view(view)?;
+                let offset = usize_property(accessor, "byteOffset").unwrap_or(0);
+                let stride = stride.unwrap_or(element_size);
+                if data_extent(offset, count, stride, element_size).map_or(true, |end| end > data.len()) {
+                    return Err(invalid("it reads past the end of its buffer view"));
+                }
+                Some((data, offset, stride))
+            }
+
+            None => {
+                if count > self.buffers.iter().map(Vec::len).max().unwrap_or(0) {
+                    return Err(invalid("it has no buffer view and a count larger than any buffer"));
+                }
+                None
+            }
+        };
+
+        let mut elements = vec![vec![0.0; columns * rows]; count];
+        if let Some((data, offset, stride)) = view {
+            read_elements(data, offset, stride, &mut elements)?;
+        }
+
+        if let Some(sparse) = accessor.get("sparse") {
+            let sparse_count = usize_property(sparse, "count").ok_or_else(|| invalid("its sparse values have no count"))?;
+            let sparse_indices = sparse.get("indices").ok_or_else(|| invalid("its sparse values have no indices"))?;
+            let sparse_values = sparse.get("values").ok_or_else(|| invalid("its sparse values have no values"))?;
+
+            // The indices and values are both tightly packed, regardless of the stride of their
+            // buffer views.
+            let index_type = usize_property(sparse_indices, "componentType")
+                .ok_or_else(|| invalid("its sparse indices have no componentType"))?;
+            let index_size = component_size(index_type).ok_or_else(|| invalid("its sparse indices have an unknown componentType"))?;
+            let (index_data, _) = self.buffer_view(
+                usize_property(sparse_indices, "bufferView").ok_or_else(|| invalid("its sparse indices have no bufferView"))?,
+            )?;
+            let index_offset = usize_property(sparse_indices, "byteOffset").unwrap_or(0);
+
+            let (value_data, _) = self.buffer_view(
+                usize_property(sparse_values, "bufferView").ok_or_else(|| invalid("its sparse values have no bufferView"))?,
+            )?;
+            let value_offset = usize_property(sparse_values, "byteOffset").unwrap_or(0);
+            if data_extent(index_offset, sparse_count, index_size, index_size).map_or(true, |end| end > index_data.len()) {
+                return Err(invalid("its sparse indices read past the end of their buffer view"));
+            }
+            if data_extent(value_offset, sparse_count, element_size, element_size).map_or(true, |end| end > value_data.len()) {
+                return Err(invalid("its sparse values read past the end of their buffer view"));
+            }
+
+            let mut values = vec![vec![0.0; columns * rows]; sparse_count];
+            read_elements(value_data, value_offset, element_size, &mut values)?;
+
+            for (sparse_index, value) in values.into_iter().enumerate() {
+                let at = index_offset + sparse_index * index_size;
+                let bytes = index_data.get(at..at + index_size)
+                    .ok_or_else(|| invalid("its sparse indices read past the end of their buffer view"))?;
+                let target = read_component(bytes, index_type, false) as usize;
+                *elements.get_mut(target).ok_or_else(|| invalid("one of its sparse indices is out of range"))? = value;
+            }
+        }
+
+        Ok(elements)
+    }
+
+    /// Returns the data of a buffer view, along with its byte stride if it has one.
+    fn buffer_view(&self, index: usize) -> Result<(&[u8], Option<usize>), LoadError> {
+        let object = format!("buffer view {}", index);
+        let invalid = |reason| LoadError::InvalidObject { object: object.clone(), reason };
+        let view = array(self.json, "bufferViews").get(index).ok_or_else(|| invalid("it doesn't exist"))?;
+
+        let buffer = usize_property(view, "buffer")
+            .and_then(|buffer| self.buffers.get(buffer))
+            .ok_or_else(|| invalid("its buffer doesn't exist"))?;
+        let offset = usize_property(view, "byteOffset").unwrap_or(0);
+        let length = usize_property(view, "byteLength").ok_or_else(|| invalid("it has no byteLength"))?;
+        let data = offset.checked_add(length)
+            .and_then(|end| buffer.get(offset..end))
+            .ok_or_else(|| invalid("it's larger than its buffer"))?;
+
+        Ok((data, usize_property(view, "byteStride")))
+    }
+
+    /// Loads every material in the file, along with the textures they use.
+    ///
+    /// The base color becomes the diffuse color, and the specular color and shininess are
+    /// derived from the metalness and roughness the same way most PBR renderers blend between
+    /// dielectric and metallic surfaces. Unlit materials use constant shading.
+    fn load_materials(&self) -> Result<(Vec<scene::Material>, Vec<Texture>), LoadError> {
+        let mut textures = TextureCache { document: self, textures: Vec::new(), loaded: HashMap::new() };
+        let mut materials = Vec::new();
+        for (index, material) in array(self.json, "materials").iter().enumerate() {
+            let empty = Value::Null;
+            let pbr = material.get("pbrMetallicRoughness").unwrap_or(&empty);
+            let base_color = numbers(pbr, "baseColorFactor").unwrap_or_else(|| vec![1.0, 1.0, 1.0, 1.0]);
+            if base_color.len() < 4 {
+                return Err(LoadError::InvalidObject {
+                    object: format!("material {}", index),
+                    reason: "its baseColorFactor doesn't have four components",
+                });
+            }
+
+            let metallic = number(pbr, "metallicFactor").unwrap_or(1.0);
+            let roughness = number(pbr, "roughnessFactor").unwrap_or(1.0);
+            let emission = numbers(material, "emissiveFactor").unwrap_or_else(|| vec![0.0, 0.0, 0.0]);
+            let unlit = material.get("extensions")
+                .and_then(|extensions| extensions.get("KHR_materials_unlit"))
+                .is_some();
+
+            // Dielectrics reflect about 4% of incoming light, while metals reflect their base
+            // color. Shininess roughly matches the width of the specular highlight.
+            let specular = |channel: usize| 0.04 + (base_color[channel] - 0.04) * metallic;
+            let alpha = roughness * roughness;
+            let shininess = (2.0 / (alpha * alpha).max(1e-4) - 2.0).max(1.0).min(512.0);
+
+            let blend = string(material, "alphaMode") == Some("BLEND");
+            let texture_index = |info: Option<&Value>| info.and_then(|info| usize_property(info, "index"));
+            let diffuse_texture = texture_index(pbr.get("baseColorTexture"));
+            let normal_texture = texture_index(material.get("normalTexture"));
+
+            let (shading, emission) = if unlit {
+                (Shading::Constant, Color::new(base_color[0], base_color[1], base_color[2], 1.0))
+            } else {
+                (Shading::Phong, Color::rgb(
+                    emission.get(0).cloned().unwrap_or(0.0),
+                    emission.get(1).cloned().unwrap_or(0.0),
+                    emission.get(2).cloned().unwrap_or(0.0),
+                ))
+            };
+
+            materials.push(scene::Material {
+                id: Some(index.to_string()),
+                name: string(material, "name").map(String::from),
+                shading,
+                diffuse: Color::rgb(base_color[0], base_color[1], base_color[2]),
+                specular: Color::rgb(specular(0), specular(1), specular(2)),
+                shininess,
+                emission,
+                ambient: Color::rgb(0.0, 0.0, 0.0),
+                transparency: if blend { 1.0 - base_color[3] } else { 0.0 },
+                diffuse_texture: match diffuse_texture {
+                    Some(texture) => Some(textures.load(texture)?),
+                    None => None,
+                },
+                specular_texture: None,
+                normal_texture: match normal_texture {
+                    Some(texture) => Some(textures.load(texture)?),
+                    None => None,
+                },
+            });
+        }
+
+        Ok((materials, textures.textures))
+    }
+}
+
+/// Loads the images referenced by textures, loading each image only once.
+struct TextureCache<'a, 'b: 'a> {
+    document: &'a Document<'b>,
+    textures: Vec<Texture>,
+
+    /// Maps image indices to indices in `textures`.
+    loaded: HashMap<usize, usize>,
+}
+
+impl<'a, 'b> TextureCache<'a, 'b> {
+    /// Returns the index of the texture loaded for the glTF texture at `index`, loading its
+    /// image if necessary.
+    ///
+    /// Images that can't be loaded are replaced with a placeholder rather than failing the
+    /// whole file, matching how missing textures are handled by the other loaders.
+    fn load(&mut self, index: usize) -> Result<usize, LoadError> {
+        let object = format!("texture {}", index);
+        let gltf_texture = array(self.document.json, "textures").get(index)
+            .ok_or_else(|| LoadError::InvalidObject { object: object.clone(), reason: "it doesn't exist" })?;
+        let image_index = usize_property(gltf_texture, "source")
+            .ok_or_else(|| LoadError::InvalidObject { object: object.clone(), reason: "it has no source image" })?;
+
+        if let Some(&texture) = self.loaded.get(&image_index) {
+            return Ok(texture);
+        }
+
+        let image = array(self.document.json, "images").get(image_index)
+            .ok_or_else(|| LoadError::InvalidObject { object, reason: "its source image doesn't exist" })?;
+        let description = format!("image {}", image_index);
+        let texture = match (string(image, "uri"), usize_property(image, "bufferView")) {
+            (Some(uri), _) if uri.starts_with("data:") => {
+                Texture::from_memory(&read_uri(self.document.base, uri)?, &description)
+            }
+
+            (Some(uri), _) => {
+                if uri.contains("://") && !uri.starts_with("file:") {
+                    println!("WARNING: Can't load {} from {:?}, using a placeholder", description, uri);
+                    Texture::checkerboard()
+                } else {
+                    Texture::load(&texture::resolve_uri(self.document.base, uri))
+                }
+            }
+
+            (None, Some(view)) => Texture::from_memory(self.document.buffer_view(view)?.0, &description),
+
+            (None, None) => {
+                println!("WARNING: {} has no data, using a placeholder", description);
+                Texture::checkerboard()
+            }
+        };
+
+        let texture_index = self.textures.len();
+        self.textures.push(texture);
+        self.loaded.insert(image_index, texture_index);
+        Ok(texture_index)
+    }
+}
+
+/// Loads every camera in the file. glTF cameras are always in meters.
+fn load_cameras(json: &Value) -> Vec<scene::Camera> {
+    let empty = Value::Null;
+    let mut cameras = Vec::new();
+    for camera in array(json, "cameras") {
+        let (projection, znear, zfar) = match string(camera, "type") {
+            Some("orthographic") => {
+                let orthographic = camera.get("orthographic").unwrap_or(&empty);
+                let projection = scene::Projection::Orthographic {
+                    xmag: number(orthographic, "xmag"),
+                    ymag: number(orthographic, "ymag"),
+                    aspect_ratio: None,
+                };
+                (projection, number(orthographic, "znear"), number(orthographic, "zfar"))
+            }
+
+            _ => {
+                let perspective = camera.get("perspective").unwrap_or(&empty);
+                let projection = scene::Projection::Perspective {
+                    xfov: None,
+                    yfov: number(perspective, "yfov").map(f32::to_degrees),
+                    aspect_ratio: number(perspective, "aspectRatio"),
+                };
+                (projection, number(perspective, "znear"), number(perspective, "zfar"))
+            }
+        };
+
+        // Perspective cameras may leave out the far plane to use an infinite projection, which
+        // the renderer doesn't support, so use a far plane that's distant enough for any
+        // sensible scene instead.
+        cameras.push(scene::Camera {
+            id: None,
+            name: string(camera, "name").map(String::from),
+            projection,
+            znear: znear.unwrap_or(0.1),
+            zfar: zfar.unwrap_or(1000.0),
+        });
+    }
+
+    cameras
+}
+
+/// Builds the scene's node list from the node hierarchy of the file's default scene.
+///
+/// Files without a default scene use their first scene, and files without any scenes use every
+/// node that isn't the child of another node as a root.
+fn load_nodes(json: &Value, scene: &Scene) -> Result<Vec<Node>, LoadError> {
+    let gltf_nodes = array(json, "nodes");
+    let roots = match array(json, "scenes").get(usize_property(json, "scene").unwrap_or(0)) {
+        Some(gltf_scene) => {
+            array(gltf_scene, "nodes").iter()
+                .filter_map(Value::as_u64)
+                .map(|node| node as usize)
+                .collect::<Vec<_>>()
+        }
+
+        None => {
+            let children = gltf_nodes.iter()
+                .flat_map(|node| array(node, "children").iter().filter_map(Value::as_u64))
+                .map(|node| node as usize)
+                .collect::<HashSet<_>>();
+            (0..gltf_nodes.len()).filter(|node| !children.contains(node)).collect::<Vec<_>>()
+        }
+    };
+
+    // Every mesh uses the indices of its materials as its material symbols.
+    let materials = (0..scene.materials.len())
+        .map(|index| (index.to_string(), index))
+        .collect::<HashMap<_, _>>();
+
+    // Walk the hierarchy depth first so that every node comes after its parent. The stack is
+    // reversed so that siblings keep their order.
+    let mut nodes = Vec::new();
+    let mut visited = HashSet::new();
+    let mut stack = roots.into_iter().rev().map(|root| (root, None)).collect::<Vec<_>>();
+    while let Some((index, parent)) = stack.pop() {
+        let object = format!("node {}", index);
+        let invalid = |reason| LoadError::InvalidObject { object: object.clone(), reason };
+        let gltf_node = gltf_nodes.get(index).ok_or_else(|| invalid("it doesn't exist"))?;
+        if !visited.insert(index) {
+            return Err(invalid("it appears more than once in the node hierarchy"));
+        }
+
+        let mut node = Node {
+            name: string(gltf_node, "name").map(String::from),
+            parent,
+            transforms: node_transforms(gltf_node),
+            .. Node::default()
+        };
+
+        if let Some(mesh) = usize_property(gltf_node, "mesh") {
+            if mesh >= scene.geometries.len() {
+                return Err(invalid("its mesh doesn't exist"));
+            }
+            node.geometries.push(GeometryInstance { geometry: mesh, materials: materials.clone() });
+        }
+
+        if let Some(camera) = usize_property(gltf_node, "camera") {
+            if camera >= scene.cameras.len() {
+                return Err(invalid("its camera doesn't exist"));
+            }
+            node.cameras.push(camera);
+        }
+
+        let node_index = nodes.len();
+        nodes.push(node);
+        for child in array(gltf_node, "children").iter().rev().filter_map(Value::as_u64) {
+            stack.push((child as usize, Some(node_index)));
+        }
+    }
+
+    Ok(nodes)
+}
+
+/// Returns the local transform of a node, which is either a column-major matrix or a
+/// translation, rotation, and scale that are applied in scale, rotation, translation order.
+fn node_transforms(node: &Value) -> Vec<Transform> {
+    let transform = |kind| Transform { sid: None, kind };
+
+    if let Some(values) = numbers(node, "matrix") {
+        return vec![transform(TransformKind::Matrix(Matrix::from_column_major(&values)))];
+    }
+
+    let mut transforms = Vec::new();
+    if let Some(values) = numbers(node, "translation") {
+        if values.len() >= 3 {
+            transforms.push(transform(TransformKind::Translate([values[0], values[1], values[2]])));
+        }
+    }
+
+    // Rotations are unit quaternions, which are converted into an axis and an angle.
+    if let Some(values) = numbers(node, "rotation") {
+        if values.len() >= 4 {
+            let w = values[3].max(-1.0).min(1.0);
+            let angle = 2.0 * w.acos();
+            let sin = (1.0 - w * w).sqrt();
+            if sin > 1e-6 {
+                transforms.push(transform(TransformKind::Rotate([
+                    values[0] / sin,
+                    values[1] / sin,
+                    values[2] / sin,
+                    angle.to_degrees(),
+                ])));
+            }
+        }
+    }
+
+    if let Some(values) = numbers(node, "scale") {
+        if values.len() >= 3 {
+            transforms.push(transform(TransformKind::Scale([values[0], values[1], values[2]])));
+        }
+    }
+
+    transforms
+}
+
+/// Returns the size in bytes of an accessor component type, or `None` if the type is unknown.
+fn component_size(component_type: usize) -> Option<usize> {
+    match component_type {
+        5120 | 5121 => Some(1),
+        5122 | 5123 => Some(2),
+        5125 | 5126 => Some(4),
+        _ => None,
+    }
+}
+
+/// Reads a single little-endian accessor component. Normalized components are mapped from the
+/// range of their integer type onto [0, 1] for unsigned types or [-1, 1] for signed types.
+fn read_component(bytes: &[u8], component_type: usize, normalized: bool) -> f64 {
+    let unsigned = bytes.iter().rev().fold(0u32, |value, &byte| value << 8 | byte as u32);
+    let (value, max) = match component_type {
+        5120 => (unsigned as u8 as i8 as f64, 127.0),
+        5121 => (unsigned as f64, 255.0),
+        5122 => (unsigned as u16 as i16 as f64, 32767.0),
+        5123 => (unsigned as f64, 65535.0),
+        5126 => { return f32::from_bits(unsigned) as f64; }
+        _ => (unsigned as f64, 1.0),
+    };
+
+    if normalized { (value / max).max(-1.0) } else { value }
+}
+
+/// Returns the array property `name` of `value`, or an empty slice if there isn't one.
+fn array<'a>(value: &'a Value, name: &str) -> &'a [Value] {
+    value.get(name).and_then(Value::as_array).map(Vec::as_slice).unwrap_or(&[])
+}
+
+fn string<'a>(value: &'a Value, name: &str) -> Option<&'a str> {
+    value.get(name).and_then(Value::as_str)
+}
+
+fn usize_property(value: &Value, name: &str) -> Option<usize> {
+    value.get(name).and_then(Value::as_u64).map(|value| value as usize)
+}
+
+fn number(value: &Value, name: &str) -> Option<f32> {
+    value.get(name).and_then(Value::as_f64).map(|value| value as f32)
+}
+
+fn numbers(value: &Value, name: &str) -> Option<Vec<f32>> {
+    value.get(name)
+        .and_then(Value::as_array)
+        .and_then(|values| values.iter().map(|value| value.as_f64().map(|value| value as f32)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("resources").join(name)
+    }
+
+    fn xyz(point: Point) -> (f32, f32, f32) {
+        (point.x, point.y, point.z)
+    }
+
+    #[test]
+    fn load_scene_substitutes_sparse_values() {
+        let scene = load_scene(fixture("sparse_triangle.gltf"), &LoadOptions::default()).unwrap();
+        assert_eq!(scene.geometries.len(), 1);
+        assert_eq!(scene.geometries[0].meshes.len(), 1);
+
+        // The sparse accessor replaces the third position, (0, 1, 0), with (0.5, 1, 0).
+        let mesh = &scene.geometries[0].meshes[0];
+        assert_eq!(mesh.vertices.len(), 3);
+        assert_eq!(mesh.indices, vec![0, 1, 2]);
+        assert_eq!(xyz(mesh.vertices[0].position), (0.0, 0.0, 0.0));
+        assert_eq!(xyz(mesh.vertices[1].position), (1.0, 0.0, 0.0));
+        assert_eq!(xyz(mesh.vertices[2].position), (0.5, 1.0, 0.0));
+        assert_eq!(mesh.material.as_ref().map(String::as_str), Some("0"));
+
+        // The file has no normals, so they're generated from the winding.
+        for vertex in &mesh.vertices {
+            let normal = vertex.normal.unwrap();
+            assert_eq!((normal.x, normal.y, normal.z), (0.0, 0.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn load_scene_reads_normalized_components() {
+        let scene = load_scene(fixture("normalized_triangle.gltf"), &LoadOptions::default()).unwrap();
+        let mesh = &scene.geometries[0].meshes[0];
+        assert_eq!(mesh.vertices.len(), 3);
+        assert_eq!(mesh.indices, vec![0, 1, 2]);
+
+        // Colors are unsigned bytes, so 255 maps onto 1.
+        let colors = mesh.vertices.iter()
+            .map(|vertex| vertex.color.map(|color| (color.r, color.g, color.b, color.a)))
+            .collect::<Vec<_>>();
+        assert_eq!(colors, vec![
+            Some((1.0, 0.0, 0.0, 1.0)),
+            Some((0.0, 1.0, 0.0, (128.0 / 255.0f64) as f32)),
+            Some((0.0, 0.0, 1.0, 0.0)),
+        ]);
+
+        // Texture coordinates are unsigned shorts, so 65535 maps onto 1, and are flipped
+        // vertically.
+        let texcoords = mesh.vertices.iter()
+            .map(|vertex| (vertex.texcoord[0].x, vertex.texcoord[0].y))
+            .collect::<Vec<_>>();
+        assert_eq!(texcoords, vec![(0.0, 1.0), (1.0, 1.0), (0.0, 0.0)]);
+    }
+
+    #[test]
+    fn read_component_maps_signed_normalized_values() {
+        assert_eq!(read_component(&[0x7f], 5120, true), 1.0);
+        assert_eq!(read_component(&[0x80], 5120, true), -1.0);
+        assert_eq!(read_component(&[0x81], 5120, true), -1.0);
+        assert_eq!(read_component(&[0xff, 0x7f], 5122, true), 1.0);
+        assert_eq!(read_component(&[0x00, 0x80], 5122, true), -1.0);
+        assert_eq!(read_component(&[0x80], 5120, false), -128.0);
+    }
+}
@@ -0,0 +1,205 @@
+//! Picking the loader for a file.
+//!
+//! Each file format implements `Loader` in its own module and is added to the registry in
+//! `Registry::default`. The registry picks a loader by the contents of the file first, since
+//! most formats start with some recognizable magic bytes, and falls back to the file's extension
+//! for formats that don't (e.g. OBJ and binary STL). The format can also be given by name, which
+//! skips detection entirely.
+
+use collada::ColladaLoader;
+use gltf::GltfLoader;
+use obj::ObjLoader;
+use ply::PlyLoader;
+use scene::{LoadOptions, Scene};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use stl::StlLoader;
+
+/// The number of bytes at the start of a file that are passed to `Loader::detect`.
+const HEADER_SIZE: usize = 1024;
+
+/// A loader for a single file format.
+pub trait Loader {
+    /// The name of the format, which is used to pick the format by name.
+    fn name(&self) -> &'static str;
+
+    /// The lowercase file extensions used by the format, without the leading dot.
+    fn extensions(&self) -> &'static [&'static str];
+
+    /// Returns whether `header`, which holds the first bytes of a file, looks like this format.
+    ///
+    /// Formats without any recognizable header should return `false`, so that they're picked
+    /// by extension instead.
+    fn detect(&self, header: &[u8]) -> bool;
+
+    /// Loads the file at `path` as a scene.
+    fn load(&self, path: &Path, options: &LoadOptions) -> Result<Scene, Box<dyn Error>>;
+}
+
+/// An error that occurred while picking a loader or loading a file with it.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The file couldn't be opened or read while detecting its format.
+    Io(io::Error),
+
+    /// The format given by name isn't one of the registered formats.
+    UnknownFormat {
+        name: String,
+        formats: Vec<&'static str>,
+    },
+
+    /// Neither the contents nor the extension of the file match any registered format.
+    UndetectedFormat {
+        formats: Vec<&'static str>,
+    },
+
+    /// The loader for the file's format failed.
+    Format {
+        format: &'static str,
+        error: Box<dyn Error>,
+    },
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Io(ref error) => write!(f, "Failed to read file: {}", error),
+
+            LoadError::UnknownFormat { ref name, ref formats } => write!(
+                f,
+                "Unknown format {:?}, expected one of: {}",
+                name,
+                formats.join(", "),
+            ),
+
+            LoadError::UndetectedFormat { ref formats } => write!(
+                f,
+                "Couldn't detect the file's format, specify one of: {}",
+                formats.join(", "),
+            ),
+
+            LoadError::Format { format, ref error } => write!(f, "{}: {}", format, error),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn description(&self) -> &str {
+        match *self {
+            LoadError::Io(..) => "Failed to read file",
+            LoadError::UnknownFormat { .. } => "Unknown format",
+            LoadError::UndetectedFormat { .. } => "Couldn't detect the file's format",
+            LoadError::Format { .. } => "Failed to load file",
+        }
+    }
+
+    fn cause(&self) -> Option<&dyn Error> {
+        match *self {
+            LoadError::Io(ref error) => Some(error),
+            LoadError::Format { ref error, .. } => Some(&**error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(from: io::Error) -> LoadError {
+        LoadError::Io(from)
+    }
+}
+
+/// The set of loaders that files can be loaded with.
+pub struct Registry {
+    loaders: Vec<Box<dyn Loader>>,
+}
+
+impl Registry {
+    /// Creates a registry without any loaders.
+    pub fn new() -> Registry {
+        Registry { loaders: Vec::new() }
+    }
+
+    /// Adds a loader to the registry.
+    ///
+    /// Loaders are tried in the order they're registered, so when more than one loader detects
+    /// a file or claims its extension, the first one registered is used.
+    pub fn register<L: Loader + 'static>(&mut self, loader: L) {
+        self.loaders.push(Box::new(loader));
+    }
+
+    /// Returns the names of the registered formats.
+    pub fn formats(&self) -> Vec<&'static str> {
+        self.loaders.iter().map(|loader| loader.name()).collect()
+    }
+
+    /// Returns the loader for the format called `name`, ignoring case.
+    pub fn find(&self, name: &str) -> Option<&dyn Loader> {
+        self.loaders.iter()
+            .find(|loader| loader.name().eq_ignore_ascii_case(name))
+            .map(|loader| &**loader)
+    }
+
+    /// Picks the loader for the file at `path`, first by the file's contents and then by its
+    /// extension.
+    pub fn detect(&self, path: &Path) -> Result<&dyn Loader, LoadError> {
+        let mut header = Vec::with_capacity(HEADER_SIZE);
+        File::open(path)?.take(HEADER_SIZE as u64).read_to_end(&mut header)?;
+        if let Some(loader) = self.loaders.iter().find(|loader| loader.detect(&header)) {
+            return Ok(&**loader);
+        }
+
+        let extension = path.extension().map(|extension| extension.to_string_lossy().to_lowercase());
+        extension
+            .and_then(|extension| {
+                self.loaders.iter().find(|loader| loader.extensions().iter().any(|&candidate| candidate == extension))
+            })
+            .map(|loader| &**loader)
+            .ok_or_else(|| LoadError::UndetectedFormat { formats: self.formats() })
+    }
+
+    /// Loads the file at `path` as a scene.
+    ///
+    /// The file is loaded as the format called `format` if one is given, otherwise the format is
+    /// detected from the file.
+    pub fn load<P: AsRef<Path>>(&self, path: P, format: Option<&str>, options: &LoadOptions) -> Result<Scene, LoadError> {
+        let path = path.as_ref();
+        let loader = match format {
+            Some(name) => self.find(name).ok_or_else(|| LoadError::UnknownFormat {
+                name: name.into(),
+                formats: self.formats(),
+            })?,
+            None => self.detect(path)?,
+        };
+
+        loader.load(path, options).map_err(|error| LoadError::Format { format: loader.name(), error })
+    }
+}
+
+impl Default for Registry {
+    /// Creates a registry with every built-in format.
+    fn default() -> Registry {
+        let mut registry = Registry::new();
+        registry.register(ColladaLoader);
+        registry.register(GltfLoader);
+        registry.register(ObjLoader);
+        registry.register(PlyLoader);
+        registry.register(StlLoader);
+        registry
+    }
+}
+
+/// Returns whether `needle` appears anywhere in `haystack`.
+pub fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|window| window == needle)
+}
+
+/// Returns `header` without any leading UTF-8 byte order mark or whitespace, for text formats
+/// whose magic word may not be at the very start of the file.
+pub fn trim_start(header: &[u8]) -> &[u8] {
+    let header = if header.starts_with(b"\xEF\xBB\xBF") { &header[3..] } else { header };
+    let start = header.iter().position(|byte| !byte.is_ascii_whitespace()).unwrap_or(header.len());
+    &header[start..]
+}
@@ -1,13 +1,23 @@
+extern crate base64;
 extern crate collaborate;
 extern crate gl_winit;
 extern crate image;
 extern crate polygon;
+extern crate serde_json;
 extern crate structopt;
 #[macro_use]
 extern crate structopt_derive;
 extern crate winit;
+extern crate xml;
 
+use animation::ClipRange;
 use gl_winit::CreateContext;
+use loader::Registry;
+use matrix::Matrix;
+use mesh::{Attribute, MeshData};
+use morphing::{TargetWeight, WeightOverrides};
+use normals::NormalMode;
+use scene::{LightKind, LoadOptions, Projection, Shading, UpAxis};
 use polygon::*;
 use polygon::anchor::*;
 use polygon::camera::*;
@@ -15,25 +25,164 @@ use polygon::gl::GlRender;
 use polygon::light::*;
 use polygon::math::*;
 use polygon::mesh_instance::*;
+use polygon::texture::*;
+use std::mem;
 use std::path::PathBuf;
+use std::process;
 use std::time::*;
 use structopt::StructOpt;
 use winit::*;
 
+mod animation;
 mod collada;
+mod gltf;
+mod loader;
+mod matrix;
+mod mesh;
+mod morphing;
+mod normals;
+mod obj;
+mod ply;
+mod scene;
+mod skinning;
+mod stl;
+mod tangents;
+mod texture;
+mod triangulate;
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "polyview", about = "A mesh viewer for the Polygon rendering engine.")]
 struct CliArgs {
     #[structopt(help = "The path to the mesh to be viewed")]
     path: String,
+
+    #[structopt(long = "format", help = "The format of the file, instead of detecting it from the file's contents and extension")]
+    format: Option<String>,
+
+    #[structopt(
+        long = "show",
+        help = "The vertex attribute to shade with: normal, color, tangent, or binormal",
+        default_value = "normal"
+    )]
+    show: Attribute,
+
+    #[structopt(long = "no-weld", help = "Give every face corner its own vertex instead of merging identical ones")]
+    no_weld: bool,
+
+    #[structopt(long = "up-axis", help = "Override the file's up axis: x, y, or z")]
+    up_axis: Option<UpAxis>,
+
+    #[structopt(long = "unit", help = "Override the file's unit size, in meters")]
+    unit: Option<f32>,
+
+    #[structopt(
+        long = "normals",
+        help = "How to generate normals for meshes without them: flat or smooth",
+        default_value = "smooth"
+    )]
+    normals: NormalMode,
+
+    #[structopt(
+        long = "crease-angle",
+        help = "The angle in degrees above which smooth normals aren't averaged across an edge",
+        default_value = "45"
+    )]
+    crease_angle: f32,
+
+    #[structopt(long = "regenerate-normals", help = "Generate normals for every mesh, ignoring the file's normals")]
+    regenerate_normals: bool,
+
+    #[structopt(long = "default-lights", help = "Ignore the file's lights and use the built-in lighting")]
+    default_lights: bool,
+
+    #[structopt(long = "bind-pose", help = "Show skinned meshes in their bind pose instead of posed by their skeleton")]
+    bind_pose: bool,
+
+    #[structopt(long = "clip", help = "The range of the animation to play, in seconds, as start:end")]
+    clip: Option<ClipRange>,
+
+    #[structopt(
+        long = "weight",
+        help = "Set the weight of a morph target, as target=weight, where the target is the name or index of the target"
+    )]
+    weights: Vec<TargetWeight>,
 }
 
 fn main() {
     let args = CliArgs::from_args();
 
-    // Build a triangle mesh.
-    let mesh = collada::load_mesh(args.path).unwrap();
+    // Load the scene from the file, picking the loader based on the file's contents and extension
+    // unless the format was given.
+    let options = LoadOptions {
+        weld_vertices: !args.no_weld,
+        up_axis: args.up_axis,
+        meters_per_unit: args.unit,
+        normal_mode: args.normals,
+        crease_angle: args.crease_angle,
+        regenerate_normals: args.regenerate_normals,
+    };
+    let registry = Registry::default();
+    let result = registry.load(&args.path, args.format.as_ref().map(String::as_str), &options);
+    let mut scene = match result {
+        Ok(scene) => scene,
+        Err(error) => {
+            eprintln!("Failed to load {:?}: {}", args.path, error);
+            process::exit(1);
+        }
+    };
+
+    // Normal maps need tangents, but most exporters don't write them. Normal maps aren't bound
+    // (see `create_material`), so the tangents are only shown with `--show tangent`.
+    tangents::generate_for_normal_maps(&mut scene);
+
+    // Play the whole animation unless the user picked a range, starting out posed at the start of
+    // the range.
+    let clip = match (args.clip, scene.animation_range()) {
+        (Some(clip), Some(_)) => Some(clip),
+        (None, Some((start, end))) => Some(ClipRange { start, end }),
+        (Some(_), None) => {
+            println!("WARNING: Ignoring --clip, the document has no animations");
+            None
+        }
+        (None, None) => None,
+    };
+    let mut time = clip.map_or(0.0, |clip| clip.start);
+    let mut playing = true;
+    if let Some(clip) = clip {
+        scene.animate(time);
+        println!(
+            "Playing animation from {}s to {}s. Space to play or pause, left and right arrows to scrub.",
+            clip.start,
+            clip.end,
+        );
+    }
+
+    // Weights from the command line and the arrow keys are kept as overrides that are applied
+    // every time the scene is posed, so that they take priority over any animation of the weights.
+    let mut weight_overrides = WeightOverrides::new();
+    for target_weight in &args.weights {
+        let mut found = false;
+        for (morph_index, morph) in scene.morphs.iter().enumerate() {
+            if let Some(target) = morphing::find_target(morph, &target_weight.target) {
+                weight_overrides.insert((morph_index, target), target_weight.weight);
+                found = true;
+            }
+        }
+
+        if !found {
+            println!("WARNING: No morph has a target named {:?}", target_weight.target);
+        }
+    }
+    morphing::apply_overrides(&mut scene.morphs, &weight_overrides);
+
+    for camera in &scene.cameras {
+        if let Projection::Orthographic { .. } = camera.projection {
+            println!(
+                "WARNING: Orthographic camera {:?} will be approximated with a perspective projection",
+                camera.key(),
+            );
+        }
+    }
 
     // Open a window.
     let mut events_loop = EventsLoop::new();
@@ -46,57 +195,319 @@ fn main() {
     let context = window.create_context().expect("Failed to create GL context");
     let mut renderer = GlRender::new(context).expect("Failed to create GL renderer");
 
-    // Send the mesh to the GPU.
-    let gpu_mesh = renderer.register_mesh(&mesh);
+    // Meshes that aren't bound to a material in the document use a plain red material.
+    let mut default_material = renderer.default_material();
+    default_material.set_color("surface_color", Color::rgb(1.0, 0.0, 0.0));
+    default_material.set_color("surface_specular", Color::rgb(1.0, 1.0, 1.0));
+    default_material.set_f32("surface_shininess", 4.0);
 
-    // Create an anchor and register it with the renderer.
-    let mut anchor = Anchor::new();
-    anchor.set_position(Point::new(0.0, 0.0, 0.0));
-    let mesh_anchor_id = renderer.register_anchor(anchor);
+    // Send each texture to the GPU.
+    let gpu_textures = scene.textures.iter()
+        .map(|texture| {
+            let texture = Texture2d::new(
+                DataFormat::Rgba,
+                TextureData::u8(texture.data.clone()),
+                texture.width as usize,
+                texture.height as usize,
+            );
+            renderer.register_texture(&texture)
+        })
+        .collect::<Vec<_>>();
 
-    let mut material = renderer.default_material();
-    material.set_color("surface_color", Color::rgb(1.0, 0.0, 0.0));
-    material.set_color("surface_specular", Color::rgb(1.0, 1.0, 1.0));
-    material.set_f32("surface_shininess", 4.0);
-
-    // Create a mesh instance, attach it to the anchor, and register it with the renderer.
-    let mut mesh_instance = MeshInstance::with_owned_material(gpu_mesh, material);
-    mesh_instance.set_anchor(mesh_anchor_id);
-    renderer.register_mesh_instance(mesh_instance);
-
-    // Create a camera and an anchor for it.
-    let mut camera_anchor = Anchor::new();
-    camera_anchor.set_position(Point::new(0.0, 0.0, 10.0));
-    let camera_anchor_id = renderer.register_anchor(camera_anchor);
-
-    // Create the light and an anchor for it.
-    let light = Light::directional(Vector3::new(1.0, -1.0, -1.0), 0.25, Color::rgb(1.0, 1.0, 1.0));
-    renderer.register_light(light);
+    // Send each mesh to the GPU.
+    let mut gpu_meshes = Vec::with_capacity(scene.geometries.len());
+    for geometry in &scene.geometries {
+        let mut geometry_meshes = Vec::with_capacity(geometry.meshes.len());
+        for mesh in &geometry.meshes {
+            geometry_meshes.push(register_mesh(&mut renderer, mesh, args.show, geometry.key()));
+        }
 
-    let mut camera = Camera::default();
-    camera.set_anchor(camera_anchor_id);
-    renderer.register_camera(camera);
+        gpu_meshes.push(geometry_meshes);
+    }
+
+    // Create an anchor for each node that has geometry, then create a mesh instance for each of
+    // the node's meshes, attach it to the anchor, and register it with the renderer. Morphed
+    // meshes are blended on the CPU, so the mesh instances and their GPU meshes are kept so that
+    // the meshes can be blended again when the weights change.
+    let mut world_transforms = scene.world_transforms();
+    let mut node_anchors = Vec::new();
+    let mut morphed_meshes = Vec::new();
+    for (node_index, node) in scene.nodes.iter().enumerate() {
+        if node.geometries.is_empty() && node.morphs.is_empty() {
+            continue;
+        }
+
+        let mut anchor = Anchor::new();
+        set_anchor_transform(&mut anchor, world_transforms[node_index]);
+        let anchor_id = renderer.register_anchor(anchor);
+        node_anchors.push((anchor_id, node_index));
+
+        for instance in &node.geometries {
+            let meshes = gpu_meshes[instance.geometry].iter().zip(&scene.geometries[instance.geometry].meshes);
+            for (&gpu_mesh, mesh) in meshes {
+                let material = mesh_material(
+                    &renderer,
+                    &scene.materials,
+                    instance.material_for(mesh),
+                    mesh,
+                    &gpu_textures,
+                    &default_material,
+                );
+
+                let mut mesh_instance = MeshInstance::with_owned_material(gpu_mesh, material);
+                mesh_instance.set_anchor(anchor_id);
+                renderer.register_mesh_instance(mesh_instance);
+            }
+        }
+
+        for instance in &node.morphs {
+            let morph = &scene.morphs[instance.morph];
+            let mut mesh_instances = Vec::with_capacity(morph.meshes.len());
+            for mesh in &morphing::blend(morph) {
+                let gpu_mesh = register_mesh(&mut renderer, mesh, args.show, morph.key());
+                let material = mesh_material(
+                    &renderer,
+                    &scene.materials,
+                    instance.material_for(mesh),
+                    mesh,
+                    &gpu_textures,
+                    &default_material,
+                );
+
+                let mut mesh_instance = MeshInstance::with_owned_material(gpu_mesh, material);
+                mesh_instance.set_anchor(anchor_id);
+                mesh_instances.push((renderer.register_mesh_instance(mesh_instance), gpu_mesh));
+            }
+
+            morphed_meshes.push((instance.morph, mesh_instances));
+        }
+    }
+
+    if !scene.morphs.is_empty() {
+        println!("Press 1-9 to select a morph target, and the up and down arrows to change its weight.");
+    }
+
+    // Skinned meshes are deformed on the CPU into world space, so each skin instance gets its own
+    // copy of the meshes on an anchor with no transform of its own. The mesh instances and their
+    // GPU meshes are kept so that the meshes can be deformed again when the skeleton moves.
+    let mut skin_anchors = Vec::new();
+    let mut skinned_meshes = Vec::new();
+    for (node_index, node) in scene.nodes.iter().enumerate() {
+        for (instance_index, instance) in node.skins.iter().enumerate() {
+            let skin = &scene.skins[instance.skin];
+            let meshes = if args.bind_pose {
+                skinning::bind_pose(skin)
+            } else {
+                skinning::pose(skin, instance, &world_transforms)
+            };
+
+            let anchor_id = renderer.register_anchor(Anchor::new());
+            skin_anchors.push(anchor_id);
+
+            let mut mesh_instances = Vec::with_capacity(meshes.len());
+            for mesh in &meshes {
+                let gpu_mesh = register_mesh(&mut renderer, mesh, args.show, skin.key());
+                let material = mesh_material(
+                    &renderer,
+                    &scene.materials,
+                    instance.material_for(mesh),
+                    mesh,
+                    &gpu_textures,
+                    &default_material,
+                );
+
+                let mut mesh_instance = MeshInstance::with_owned_material(gpu_mesh, material);
+                mesh_instance.set_anchor(anchor_id);
+                mesh_instances.push((renderer.register_mesh_instance(mesh_instance), gpu_mesh));
+            }
+
+            skinned_meshes.push((node_index, instance_index, mesh_instances));
+        }
+    }
+
+    // Create a camera and an anchor for it. The camera starts out looking through the
+    // document's active camera, if it has one.
+    let camera_anchor_id = renderer.register_anchor(Anchor::new());
+    let camera_id = renderer.register_camera(Camera::default());
+    let document_cameras = scene.camera_instances();
+    let mut active_camera = if document_cameras.is_empty() { None } else { Some(0) };
+    view_through(&mut renderer, camera_id, camera_anchor_id, &scene, &world_transforms, &document_cameras, active_camera);
+
+    // Create the lights from the document, or the built-in lighting rig if the document doesn't
+    // have any lights or the user asked for it.
+    let mut light_anchors = Vec::new();
+    if !args.default_lights {
+        for (node_index, node) in scene.nodes.iter().enumerate() {
+            for &index in &node.lights {
+                if let Some(anchor_id) = register_light(&mut renderer, &scene.lights[index], &world_transforms[node_index]) {
+                    light_anchors.push((anchor_id, node_index));
+                }
+            }
+        }
+    }
+
+    if light_anchors.is_empty() {
+        let light = Light::directional(Vector3::new(1.0, -1.0, -1.0), 0.25, Color::rgb(1.0, 1.0, 1.0));
+        renderer.register_light(light);
+    }
+
+    // Unless the document is animated, the whole scene slowly spins about the origin so that it
+    // can be seen from all sides when using the built-in camera. Document cameras see the scene
+    // as it was authored.
+    let mut spin = (0.0, 0.0, 0.0);
+
+    // How far the arrow keys move through the animation.
+    const SCRUB_STEP: f32 = 1.0 / 30.0;
+
+    // How much the arrow keys change the selected morph target's weight.
+    const WEIGHT_STEP: f32 = 0.1;
+    let mut selected_target = 0;
 
     let mut loop_active = true;
     let frame_time = Duration::from_secs(1) / 60;
     let mut next_loop_time = Instant::now() + frame_time;
     while loop_active {
+        let mut cycle_camera = false;
+        let mut toggle_playback = false;
+        let mut scrub = 0.0;
+        let mut weight_change = 0.0;
         events_loop.poll_events(|event| {
             match event {
                 Event::WindowEvent { event: WindowEvent::Closed, .. } => {
                     loop_active = false;
                 }
 
+                Event::WindowEvent {
+                    event: WindowEvent::KeyboardInput {
+                        input: KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(key),
+                            ..
+                        },
+                        ..
+                    },
+                    ..
+                } => {
+                    match key {
+                        // Cycle through the document's cameras, followed by the built-in camera.
+                        VirtualKeyCode::C => { cycle_camera = true; }
+
+                        VirtualKeyCode::Space => { toggle_playback = true; }
+                        VirtualKeyCode::Left => { scrub -= SCRUB_STEP; }
+                        VirtualKeyCode::Right => { scrub += SCRUB_STEP; }
+
+                        VirtualKeyCode::Key1 => { selected_target = 0; }
+                        VirtualKeyCode::Key2 => { selected_target = 1; }
+                        VirtualKeyCode::Key3 => { selected_target = 2; }
+                        VirtualKeyCode::Key4 => { selected_target = 3; }
+                        VirtualKeyCode::Key5 => { selected_target = 4; }
+                        VirtualKeyCode::Key6 => { selected_target = 5; }
+                        VirtualKeyCode::Key7 => { selected_target = 6; }
+                        VirtualKeyCode::Key8 => { selected_target = 7; }
+                        VirtualKeyCode::Key9 => { selected_target = 8; }
+                        VirtualKeyCode::Up => { weight_change += WEIGHT_STEP; }
+                        VirtualKeyCode::Down => { weight_change -= WEIGHT_STEP; }
+                        _ => {}
+                    }
+                }
+
                 _ => {}
             }
         });
         if !loop_active { break; }
 
-        {
-            let mesh_anchor = renderer.get_anchor_mut(mesh_anchor_id).unwrap();
-            let orientation = mesh_anchor.orientation();
-            let change = Orientation::from_eulers(TAU / 4.0 / 60.0, TAU / 6.0 / 60.0, TAU / 8.0 / 60.0);
-            mesh_anchor.set_orientation(orientation + change);
+        // Advance the animation, and pose the scene if the time changed.
+        let mut pose_changed = false;
+        if let Some(clip) = clip {
+            if toggle_playback {
+                playing = !playing;
+            }
+
+            let previous_time = time;
+            if playing {
+                time += 1.0 / 60.0;
+            }
+            time = clip.wrap(time + scrub);
+            pose_changed = time != previous_time;
+        }
+
+        // Morphs are only blended again if posing the scene or the arrow keys changed a weight.
+        let previous_weights = scene.morphs.iter().map(|morph| morph.weights.clone()).collect::<Vec<_>>();
+        if pose_changed {
+            scene.animate(time);
+            morphing::apply_overrides(&mut scene.morphs, &weight_overrides);
+
+            // Skinned meshes are only deformed again if a node actually moved, since animations
+            // that only change morph weights still change the time.
+            let previous_transforms = mem::replace(&mut world_transforms, scene.world_transforms());
+            if !args.bind_pose && world_transforms != previous_transforms {
+                for &mut (node_index, instance_index, ref mut mesh_instances) in &mut skinned_meshes {
+                    let instance = &scene.nodes[node_index].skins[instance_index];
+                    let skin = &scene.skins[instance.skin];
+                    let meshes = skinning::pose(skin, instance, &world_transforms);
+                    for (mesh, mesh_instance) in meshes.iter().zip(mesh_instances) {
+                        replace_mesh(&mut renderer, mesh_instance, mesh, args.show, skin.key());
+                    }
+                }
+            }
+
+            // Directional lights ignore their anchor, so they keep their initial direction.
+            for &(anchor_id, node_index) in &light_anchors {
+                let anchor = renderer.get_anchor_mut(anchor_id).unwrap();
+                set_anchor_transform(anchor, world_transforms[node_index]);
+            }
+        }
+
+        // Change the selected target's weight in every morph that has that many targets.
+        if weight_change != 0.0 {
+            for (morph_index, morph) in scene.morphs.iter().enumerate() {
+                if selected_target < morph.weights.len() {
+                    let weight = (morph.weights[selected_target] + weight_change).max(0.0).min(1.0);
+                    weight_overrides.insert((morph_index, selected_target), weight);
+                    println!("Morph {:?} target {} weight: {:.1}", morph.key(), selected_target + 1, weight);
+                }
+            }
+            morphing::apply_overrides(&mut scene.morphs, &weight_overrides);
+        }
+
+        for &mut (morph_index, ref mut mesh_instances) in &mut morphed_meshes {
+            let morph = &scene.morphs[morph_index];
+            if morph.weights == previous_weights[morph_index] {
+                continue;
+            }
+
+            for (mesh, mesh_instance) in morphing::blend(morph).iter().zip(mesh_instances) {
+                replace_mesh(&mut renderer, mesh_instance, mesh, args.show, morph.key());
+            }
+        }
+
+        if cycle_camera {
+            active_camera = match active_camera {
+                Some(index) if index + 1 < document_cameras.len() => Some(index + 1),
+                Some(_) => None,
+                None if !document_cameras.is_empty() => Some(0),
+                None => None,
+            };
+        }
+        if cycle_camera || (pose_changed && active_camera.is_some()) {
+            view_through(&mut renderer, camera_id, camera_anchor_id, &scene, &world_transforms, &document_cameras, active_camera);
+        }
+
+        let spin_transform = if active_camera.is_none() && clip.is_none() {
+            spin.0 += TAU / 4.0 / 60.0;
+            spin.1 += TAU / 6.0 / 60.0;
+            spin.2 += TAU / 8.0 / 60.0;
+            Matrix::from_eulers(spin.0, spin.1, spin.2)
+        } else {
+            Matrix::identity()
+        };
+        for &(anchor_id, node_index) in &node_anchors {
+            let anchor = renderer.get_anchor_mut(anchor_id).unwrap();
+            set_anchor_transform(anchor, spin_transform * world_transforms[node_index]);
+        }
+        for &anchor_id in &skin_anchors {
+            let anchor = renderer.get_anchor_mut(anchor_id).unwrap();
+            set_anchor_transform(anchor, spin_transform);
         }
 
         // Render the mesh.
@@ -108,3 +519,207 @@ fn main() {
         next_loop_time += frame_time;
     }
 }
+
+/// Builds a polygon mesh from loaded mesh data and sends it to the GPU, exiting if the mesh can't
+/// be built. `owner` identifies the geometry or skin that the mesh belongs to.
+fn register_mesh(renderer: &mut GlRender, mesh: &MeshData, attribute: Attribute, owner: Option<&str>) -> GpuMesh {
+    let mesh = match mesh.build(attribute) {
+        Ok(mesh) => mesh,
+        Err(error) => {
+            eprintln!("Failed to build mesh for {:?}: {:?}", owner, error);
+            process::exit(1);
+        }
+    };
+
+    renderer.register_mesh(&mesh)
+}
+
+/// Replaces the GPU mesh drawn by a mesh instance with a newly deformed copy of the mesh.
+///
+/// The renderer can't update a mesh in place, so the old GPU mesh is freed before the new one is
+/// registered. Otherwise every frame of an animation would leak a copy of each deformed mesh.
+fn replace_mesh(
+    renderer: &mut GlRender,
+    mesh_instance: &mut (MeshInstanceId, GpuMesh),
+    mesh: &MeshData,
+    attribute: Attribute,
+    owner: Option<&str>,
+) {
+    let (mesh_instance_id, ref mut gpu_mesh) = *mesh_instance;
+    renderer.unregister_mesh(*gpu_mesh);
+    *gpu_mesh = register_mesh(renderer, mesh, attribute, owner);
+    renderer.get_mesh_instance_mut(mesh_instance_id).unwrap().set_mesh(*gpu_mesh);
+}
+
+/// Creates the material for a mesh, using the scene material at `index` if there is one and
+/// the default material otherwise.
+///
+/// The renderer has no vertex color input, so meshes with vertex colors are tinted by their
+/// average vertex color unless they have a diffuse texture. Meshes without a material take the
+/// average color as is, instead of the default material's red.
+fn mesh_material(
+    renderer: &GlRender,
+    materials: &[scene::Material],
+    index: Option<usize>,
+    mesh: &MeshData,
+    textures: &[GpuTexture],
+    default_material: &material::Material,
+) -> material::Material {
+    let vertex_color = mesh.average_color();
+    match index {
+        Some(index) => create_material(renderer, &materials[index], textures, vertex_color),
+        None => {
+            let mut material = default_material.clone();
+            if let Some(color) = vertex_color {
+                material.set_color("surface_color", color);
+            }
+            material
+        }
+    }
+}
+
+/// Creates a renderer material from a loaded material description.
+///
+/// The renderer's default material only supports a diffuse color, specular color, and
+/// shininess, so emission and ambient colors are dropped. Constant materials are unlit, so their
+/// emission color is used as the surface color instead.
+///
+/// Normal maps aren't bound either, since shading with them needs per-vertex tangents and the
+/// renderer's vertex layout has no slot for them (see the `mesh` module).
+fn create_material(
+    renderer: &GlRender,
+    description: &scene::Material,
+    textures: &[GpuTexture],
+    vertex_color: Option<Color>,
+) -> material::Material {
+    let mut material = renderer.default_material();
+
+    let (mut color, specular) = match description.shading {
+        Shading::Constant => (description.emission, Color::rgb(0.0, 0.0, 0.0)),
+        Shading::Lambert => (description.diffuse, Color::rgb(0.0, 0.0, 0.0)),
+        Shading::Phong | Shading::Blinn => (description.diffuse, description.specular),
+    };
+    if let (Some(tint), None) = (vertex_color, description.diffuse_texture) {
+        color = Color::new(color.r * tint.r, color.g * tint.g, color.b * tint.b, color.a * tint.a);
+    }
+    let alpha = color.a * (1.0 - description.transparency);
+
+    material.set_color("surface_color", Color::new(color.r, color.g, color.b, alpha));
+    material.set_color("surface_specular", specular);
+    material.set_f32("surface_shininess", description.shininess);
+
+    if let Some(index) = description.diffuse_texture {
+        material.set_texture("surface_diffuse", textures[index]);
+    }
+    if let Some(index) = description.specular_texture {
+        material.set_texture("surface_specular_map", textures[index]);
+    }
+
+    material
+}
+
+/// Registers a light from the document with the renderer, placed using the world transform of
+/// its node. Returns the light's anchor, or `None` if the renderer doesn't support the light.
+///
+/// The renderer only supports point and directional lights, so spot lights are approximated as
+/// point lights. Ambient lights are skipped.
+fn register_light(renderer: &mut GlRender, light: &scene::Light, transform: &Matrix) -> Option<AnchorId> {
+    // Point lights need a radius, so use the distance at which the light's attenuation makes it
+    // too dim to see, or an arbitrary large radius for unattenuated lights.
+    let radius = light.range(1.0 / 256.0).unwrap_or(1000.0);
+
+    let light = match light.kind {
+        LightKind::Ambient => {
+            println!("WARNING: Skipping ambient light {:?}, ambient lights are not supported", light.key());
+            return None;
+        }
+
+        LightKind::Directional => {
+            let direction = transform.transform_vector(Vector3::new(0.0, 0.0, -1.0));
+            Light::directional(direction, 1.0, light.color)
+        }
+
+        LightKind::Point => Light::point(radius, 1.0, light.color),
+
+        LightKind::Spot { .. } => {
+            println!("WARNING: Spot light {:?} will be displayed as a point light", light.key());
+            Light::point(radius, 1.0, light.color)
+        }
+    };
+
+    // Directional lights ignore their anchor, but every other light is placed by one.
+    let mut anchor = Anchor::new();
+    set_anchor_transform(&mut anchor, *transform);
+    let anchor_id = renderer.register_anchor(anchor);
+
+    let mut light = light;
+    light.set_anchor(anchor_id);
+    renderer.register_light(light);
+    Some(anchor_id)
+}
+
+/// Points the viewer's camera through one of the document's cameras, or through the built-in
+/// camera if `active` is `None`.
+///
+/// `document_cameras` is the list of camera instances in the scene, and `active` is an index into
+/// that list. The renderer has no orthographic projection, so orthographic cameras are
+/// approximated with a narrow perspective projection placed far enough back to show the same
+/// area.
+fn view_through(
+    renderer: &mut GlRender,
+    camera_id: CameraId,
+    anchor_id: AnchorId,
+    scene: &scene::Scene,
+    world_transforms: &[Matrix],
+    document_cameras: &[(usize, usize)],
+    active: Option<usize>,
+) {
+    // The window is square.
+    const VIEWPORT_ASPECT: f32 = 1.0;
+    const ORTHOGRAPHIC_FOV: f32 = 5.0;
+
+    let (camera, transform) = match active {
+        Some(index) => {
+            let (node, camera_index) = document_cameras[index];
+            let description = &scene.cameras[camera_index];
+            let size = description.projection.vertical_size(VIEWPORT_ASPECT);
+
+            match description.projection {
+                Projection::Perspective { .. } => {
+                    let camera = Camera::new(size.to_radians(), VIEWPORT_ASPECT, description.znear, description.zfar);
+                    (camera, world_transforms[node])
+                }
+
+                Projection::Orthographic { .. } => {
+                    let distance = size / (ORTHOGRAPHIC_FOV / 2.0).to_radians().tan();
+                    let camera = Camera::new(
+                        ORTHOGRAPHIC_FOV.to_radians(),
+                        VIEWPORT_ASPECT,
+                        description.znear + distance,
+                        description.zfar + distance,
+                    );
+                    (camera, world_transforms[node] * Matrix::translation(0.0, 0.0, distance))
+                }
+            }
+        }
+
+        None => (Camera::default(), Matrix::translation(0.0, 0.0, 10.0)),
+    };
+
+    let mut camera = camera;
+    camera.set_anchor(anchor_id);
+    *renderer.get_camera_mut(camera_id).unwrap() = camera;
+
+    let anchor = renderer.get_anchor_mut(anchor_id).unwrap();
+    set_anchor_transform(anchor, transform);
+}
+
+/// Sets the anchor's position, orientation, and scale from a transform matrix.
+///
+/// Anchors can't represent skew, so any skew in the transform is lost.
+fn set_anchor_transform(anchor: &mut Anchor, transform: Matrix) {
+    let (position, rotation, scale) = transform.decompose();
+    anchor.set_position(position);
+    anchor.set_orientation(rotation.orientation());
+    anchor.set_scale(scale);
+}
@@ -0,0 +1,320 @@
+//! A 4x4 transformation matrix used to compose scene graph transforms.
+//!
+//! Mesh files describe transforms as arbitrary affine matrices (or sequences of transform
+//! elements that are easiest to compose as matrices), while polygon's anchors only store a
+//! position, orientation, and scale. Transforms are composed using `Matrix` and then decomposed
+//! into the anchor's representation.
+
+use polygon::math::*;
+use std::ops::Mul;
+
+/// A 4x4 matrix stored in row-major order and applied to column vectors, which matches the
+/// layout used by COLLADA's `<matrix>` element.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix(pub [[f32; 4]; 4]);
+
+impl Matrix {
+    pub fn identity() -> Matrix {
+        Matrix([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Creates a matrix from 16 values in row-major order.
+    pub fn from_row_major(values: &[f32]) -> Matrix {
+        let mut matrix = Matrix::identity();
+        for (index, &value) in values.iter().take(16).enumerate() {
+            matrix.0[index / 4][index % 4] = value;
+        }
+
+        matrix
+    }
+
+    /// Creates a matrix from 16 values in column-major order, which is the layout used by glTF.
+    pub fn from_column_major(values: &[f32]) -> Matrix {
+        let mut matrix = Matrix::identity();
+        for (index, &value) in values.iter().take(16).enumerate() {
+            matrix.0[index % 4][index / 4] = value;
+        }
+
+        matrix
+    }
+
+    pub fn translation(x: f32, y: f32, z: f32) -> Matrix {
+        let mut matrix = Matrix::identity();
+        matrix.0[0][3] = x;
+        matrix.0[1][3] = y;
+        matrix.0[2][3] = z;
+        matrix
+    }
+
+    pub fn scale(x: f32, y: f32, z: f32) -> Matrix {
+        let mut matrix = Matrix::identity();
+        matrix.0[0][0] = x;
+        matrix.0[1][1] = y;
+        matrix.0[2][2] = z;
+        matrix
+    }
+
+    /// Creates a rotation of `radians` about `axis`, following the right-hand rule.
+    pub fn rotation(axis: [f32; 3], radians: f32) -> Matrix {
+        let [x, y, z] = normalize(axis);
+        let (sin, cos) = radians.sin_cos();
+        let t = 1.0 - cos;
+
+        Matrix([
+            [t * x * x + cos, t * x * y - sin * z, t * x * z + sin * y, 0.0],
+            [t * x * y + sin * z, t * y * y + cos, t * y * z - sin * x, 0.0],
+            [t * x * z - sin * y, t * y * z + sin * x, t * z * z + cos, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Creates a rotation from euler angles in radians, applied about the Z axis first, then
+    /// the Y axis, then the X axis. This is the inverse of `to_eulers()`.
+    pub fn from_eulers(x: f32, y: f32, z: f32) -> Matrix {
+        Matrix::rotation([1.0, 0.0, 0.0], x)
+            * Matrix::rotation([0.0, 1.0, 0.0], y)
+            * Matrix::rotation([0.0, 0.0, 1.0], z)
+    }
+
+    /// Creates a transform that places an object at `eye`, looking towards `target`, with the
+    /// object's up direction as close to `up` as possible. The object looks down its local -Z
+    /// axis.
+    pub fn look_at(eye: [f32; 3], target: [f32; 3], up: [f32; 3]) -> Matrix {
+        let back = normalize(sub(eye, target));
+        let right = normalize(cross(up, back));
+        let up = cross(back, right);
+
+        Matrix([
+            [right[0], up[0], back[0], eye[0]],
+            [right[1], up[1], back[1], eye[1]],
+            [right[2], up[2], back[2], eye[2]],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Creates a skew (shear) transform as described by COLLADA's `<skew>` element: `degrees`
+    /// of skew along `translation_axis`, relative to `rotation_axis`.
+    pub fn skew(degrees: f32, rotation_axis: [f32; 3], translation_axis: [f32; 3]) -> Matrix {
+        // This follows the RenderMan definition of skew that COLLADA uses: points are displaced
+        // along the translation axis in proportion to their distance along the component of the
+        // rotation axis that's perpendicular to the translation axis.
+        let translation = normalize(translation_axis);
+        let along = dot(rotation_axis, translation);
+        let perpendicular = normalize(sub(rotation_axis, scale_vector(translation, along)));
+        let across = dot(rotation_axis, perpendicular);
+
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let rotated_across = across * cos - along * sin;
+        let rotated_along = across * sin + along * cos;
+        let factor = if across.abs() > ::std::f32::EPSILON && rotated_across > 0.0 {
+            rotated_along / rotated_across - along / across
+        } else {
+            0.0
+        };
+
+        let mut matrix = Matrix::identity();
+        for row in 0..3 {
+            for column in 0..3 {
+                matrix.0[row][column] += factor * translation[row] * perpendicular[column];
+            }
+        }
+
+        matrix
+    }
+
+    /// Transforms a point, including the matrix's translation.
+    pub fn transform_point(&self, point: Point) -> Point {
+        let [x, y, z] = self.apply([point.x, point.y, point.z], 1.0);
+        Point::new(x, y, z)
+    }
+
+    /// Transforms a direction, ignoring the matrix's translation.
+    pub fn transform_vector(&self, vector: Vector3) -> Vector3 {
+        let [x, y, z] = self.apply([vector.x, vector.y, vector.z], 0.0);
+        Vector3 { x, y, z }
+    }
+
+    /// Transforms a normal, using the inverse transpose of the matrix so that normals stay
+    /// perpendicular to their surface under non-uniform scale. The result is normalized.
+    pub fn transform_normal(&self, normal: Vector3) -> Vector3 {
+        let [x, y, z] = normalize(self.normal_matrix().apply([normal.x, normal.y, normal.z], 0.0));
+        Vector3 { x, y, z }
+    }
+
+    /// Returns the inverse transpose of the matrix. Transforming a normal by it with
+    /// `transform_vector` and normalizing the result is the same as `transform_normal`, without
+    /// inverting the matrix for every normal.
+    ///
+    /// Matrices that can't be inverted give the identity, leaving normals unchanged.
+    pub fn normal_matrix(&self) -> Matrix {
+        let inverse = self.inverse().unwrap_or_else(Matrix::identity).0;
+        let mut transpose = [[0.0; 4]; 4];
+        for row in 0..4 {
+            for column in 0..4 {
+                transpose[row][column] = inverse[column][row];
+            }
+        }
+
+        Matrix(transpose)
+    }
+
+    fn apply(&self, vector: [f32; 3], w: f32) -> [f32; 3] {
+        let m = &self.0;
+        let mut result = [0.0; 3];
+        for row in 0..3 {
+            result[row] = m[row][0] * vector[0] + m[row][1] * vector[1] + m[row][2] * vector[2]
+                + m[row][3] * w;
+        }
+
+        result
+    }
+
+    /// Returns the determinant of the upper-left 3x3 part of the matrix. A negative determinant
+    /// means the transform mirrors geometry.
+    pub fn determinant(&self) -> f32 {
+        let m = &self.0;
+        dot([m[0][0], m[1][0], m[2][0]], cross([m[0][1], m[1][1], m[2][1]], [m[0][2], m[1][2], m[2][2]]))
+    }
+
+    /// Returns the inverse of the matrix, or `None` if the matrix isn't invertible.
+    pub fn inverse(&self) -> Option<Matrix> {
+        // Invert using Gauss-Jordan elimination with partial pivoting.
+        let mut m = self.0;
+        let mut inverse = Matrix::identity().0;
+
+        for column in 0..4 {
+            let pivot = (column..4)
+                .max_by(|&a, &b| m[a][column].abs().partial_cmp(&m[b][column].abs()).unwrap())
+                .unwrap();
+            if m[pivot][column].abs() < 1e-12 {
+                return None;
+            }
+
+            m.swap(column, pivot);
+            inverse.swap(column, pivot);
+
+            let scale = 1.0 / m[column][column];
+            for index in 0..4 {
+                m[column][index] *= scale;
+                inverse[column][index] *= scale;
+            }
+
+            for row in 0..4 {
+                if row == column {
+                    continue;
+                }
+
+                let factor = m[row][column];
+                for index in 0..4 {
+                    m[row][index] -= factor * m[column][index];
+                    inverse[row][index] -= factor * inverse[column][index];
+                }
+            }
+        }
+
+        Some(Matrix(inverse))
+    }
+
+    /// Decomposes the matrix into a translation, rotation, and scale, such that the matrix is
+    /// equivalent to `translation * rotation * scale`.
+    ///
+    /// Any skew in the matrix can't be represented and is lost. Mirroring transforms are
+    /// represented as a negative X scale.
+    pub fn decompose(&self) -> (Point, Matrix, Vector3) {
+        let m = &self.0;
+        let translation = Point::new(m[0][3], m[1][3], m[2][3]);
+
+        let column = |index: usize| [m[0][index], m[1][index], m[2][index]];
+        let mut scale = [length(column(0)), length(column(1)), length(column(2))];
+        if self.determinant() < 0.0 {
+            scale[0] = -scale[0];
+        }
+
+        let mut rotation = Matrix::identity();
+        for index in 0..3 {
+            let axis = column(index);
+            for row in 0..3 {
+                rotation.0[row][index] = if scale[index] != 0.0 { axis[row] / scale[index] } else { 0.0 };
+            }
+        }
+
+        (translation, rotation, Vector3 { x: scale[0], y: scale[1], z: scale[2] })
+    }
+
+    /// Converts a pure rotation matrix into the euler angles accepted by `from_eulers()`.
+    pub fn to_eulers(&self) -> (f32, f32, f32) {
+        let m = &self.0;
+        let y = m[0][2].max(-1.0).min(1.0).asin();
+        if m[0][2].abs() < 0.9999 {
+            (
+                (-m[1][2]).atan2(m[2][2]),
+                y,
+                (-m[0][1]).atan2(m[0][0]),
+            )
+        } else {
+            // Gimbal lock: X and Z rotate about the same axis, so put all of the rotation in X.
+            (m[2][1].atan2(m[1][1]), y, 0.0)
+        }
+    }
+
+    /// Converts the rotation part of the matrix into an orientation.
+    pub fn orientation(&self) -> Orientation {
+        let (x, y, z) = self.to_eulers();
+        Orientation::from_eulers(x, y, z)
+    }
+}
+
+impl Default for Matrix {
+    fn default() -> Matrix {
+        Matrix::identity()
+    }
+}
+
+impl Mul for Matrix {
+    type Output = Matrix;
+
+    fn mul(self, other: Matrix) -> Matrix {
+        let mut result = [[0.0; 4]; 4];
+        for row in 0..4 {
+            for column in 0..4 {
+                result[row][column] = (0..4).map(|index| self.0[row][index] * other.0[index][column]).sum();
+            }
+        }
+
+        Matrix(result)
+    }
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale_vector(a: [f32; 3], scale: f32) -> [f32; 3] {
+    [a[0] * scale, a[1] * scale, a[2] * scale]
+}
+
+fn length(a: [f32; 3]) -> f32 {
+    dot(a, a).sqrt()
+}
+
+fn normalize(a: [f32; 3]) -> [f32; 3] {
+    let length = length(a);
+    if length > 0.0 { scale_vector(a, 1.0 / length) } else { a }
+}
@@ -0,0 +1,186 @@
+//! Loader-agnostic mesh data.
+//!
+//! Polygon's `Vertex` only has slots for positions, normals, and texcoords, so the loaders build
+//! meshes out of the richer `Vertex` type defined here, and the viewer converts them into
+//! polygon meshes right before sending them to the GPU.
+//!
+//! Colors, tangents, and binormals have no slot of their own, so they never reach the GPU as
+//! vertex data. In normal shading, per-vertex colors are dropped and a mesh's material is only
+//! tinted by the mesh's average vertex color (see `MeshData::average_color`), and normal maps are
+//! left unbound since there are no tangents to shade them with. The attributes are still loaded
+//! so that they can be inspected with `--show color`, `--show tangent`, and `--show binormal`,
+//! which shade with the selected attribute in place of the normals. Uploading them properly needs
+//! a vertex layout in polygon that can carry them.
+
+use matrix::Matrix;
+use polygon::geometry::mesh::{BuildMeshError, MeshBuilder};
+use polygon::geometry::mesh::Mesh as PolygonMesh;
+use polygon::geometry::mesh::Vertex as PolygonVertex;
+use polygon::math::*;
+use std::str::FromStr;
+
+/// A single vertex with all of the attributes that can be loaded from a mesh file.
+#[derive(Debug, Clone)]
+pub struct Vertex {
+    pub position: Point,
+    pub normal: Option<Vector3>,
+    pub texcoord: Vec<Vector2>,
+    pub color: Option<Color>,
+    pub tangent: Option<Vector3>,
+    pub binormal: Option<Vector3>,
+
+    /// The index of the vertex's position in the file's position data. Skin weights and morph
+    /// targets are given per position rather than per vertex, so this is used to match them up
+    /// with the vertex.
+    pub position_index: Option<usize>,
+
+    /// The joints that influence the vertex, as pairs of an index into the skin's joint list and
+    /// the joint's weight.
+    pub joints: Vec<(usize, f32)>,
+}
+
+impl Vertex {
+    /// Creates a new vertex at `position` with no other attributes.
+    pub fn new(position: Point) -> Vertex {
+        Vertex {
+            position,
+            normal: None,
+            texcoord: Vec::new(),
+            color: None,
+            tangent: None,
+            binormal: None,
+            position_index: None,
+            joints: Vec::new(),
+        }
+    }
+}
+
+/// The vertex and index data for a single triangle mesh.
+#[derive(Debug, Clone, Default)]
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+
+    /// The material symbol for the mesh. The symbol is bound to one of the scene's materials
+    /// by each geometry instance that uses the mesh.
+    pub material: Option<String>,
+}
+
+impl MeshData {
+    /// Transforms the mesh's positions, normals, tangents, and binormals by `transform`.
+    pub fn transform(&mut self, transform: &Matrix) {
+        for vertex in &mut self.vertices {
+            vertex.position = transform.transform_point(vertex.position);
+            vertex.normal = vertex.normal.map(|normal| transform.transform_normal(normal));
+            vertex.tangent = vertex.tangent.map(|tangent| normalize(transform.transform_vector(tangent)));
+            vertex.binormal = vertex.binormal.map(|binormal| normalize(transform.transform_vector(binormal)));
+        }
+
+        // Mirroring transforms flip the winding of every triangle, so flip it back.
+        if transform.determinant() < 0.0 {
+            for triangle in self.indices.chunks_mut(3) {
+                triangle.swap(1, 2);
+            }
+        }
+    }
+
+    /// Returns the average color of the mesh's vertices, or `None` if none of them have a color.
+    pub fn average_color(&self) -> Option<Color> {
+        let mut sum = [0.0; 4];
+        let mut count = 0;
+        for color in self.vertices.iter().filter_map(|vertex| vertex.color) {
+            sum[0] += color.r;
+            sum[1] += color.g;
+            sum[2] += color.b;
+            sum[3] += color.a;
+            count += 1;
+        }
+
+        if count == 0 {
+            return None;
+        }
+
+        let count = count as f32;
+        Some(Color::new(sum[0] / count, sum[1] / count, sum[2] / count, sum[3] / count))
+    }
+
+    /// Builds a polygon mesh from the mesh data.
+    ///
+    /// The renderer's vertex layout has no slots for colors, tangents, or binormals, so those
+    /// attributes are displayed by substituting them for the vertex normal, as selected by
+    /// `attribute`. Vertices that lack the selected attribute keep their normal.
+    pub fn build(&self, attribute: Attribute) -> Result<PolygonMesh, BuildMeshError> {
+        let mut builder = MeshBuilder::new();
+        for vertex in &self.vertices {
+            let normal = match attribute {
+                Attribute::Normal => vertex.normal,
+                Attribute::Color => vertex.color.map(color_to_vector).or(vertex.normal),
+                Attribute::Tangent => vertex.tangent.or(vertex.normal),
+                Attribute::Binormal => vertex.binormal.or(vertex.normal),
+            };
+
+            builder.add_vertex(PolygonVertex {
+                position: vertex.position,
+                normal,
+                texcoord: vertex.texcoord.clone(),
+            });
+        }
+
+        builder
+            .set_indices(&*self.indices)
+            .build()
+    }
+}
+
+/// Returns `vector` scaled to unit length, or unchanged if it has zero length.
+pub fn normalize(vector: Vector3) -> Vector3 {
+    let length = (vector.x * vector.x + vector.y * vector.y + vector.z * vector.z).sqrt();
+    if length > 0.0 {
+        Vector3 { x: vector.x / length, y: vector.y / length, z: vector.z / length }
+    } else {
+        vector
+    }
+}
+
+/// Maps a color from the [0, 1] range onto a unit vector, so that different colors produce
+/// visibly different shading.
+fn color_to_vector(color: Color) -> Vector3 {
+    let vector = Vector3 {
+        x: color.r * 2.0 - 1.0,
+        y: color.g * 2.0 - 1.0,
+        z: color.b * 2.0 - 1.0,
+    };
+
+    // Mid-gray maps onto the zero vector, so give it an arbitrary direction instead.
+    if vector.x == 0.0 && vector.y == 0.0 && vector.z == 0.0 {
+        Vector3 { x: 0.0, y: 1.0, z: 0.0 }
+    } else {
+        normalize(vector)
+    }
+}
+
+/// A vertex attribute that the viewer can visualize.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attribute {
+    Normal,
+    Color,
+    Tangent,
+    Binormal,
+}
+
+impl FromStr for Attribute {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Attribute, String> {
+        match string {
+            "normal" => Ok(Attribute::Normal),
+            "color" => Ok(Attribute::Color),
+            "tangent" => Ok(Attribute::Tangent),
+            "binormal" => Ok(Attribute::Binormal),
+            _ => Err(format!(
+                "Unknown vertex attribute {:?}, expected one of normal, color, tangent, binormal",
+                string,
+            )),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! CPU blending of morph targets.
+//!
+//! The renderer has no support for morph targets, so morphed meshes are blended on the CPU and
+//! uploaded as regular meshes whenever the weights change.
+
+use mesh::{normalize, MeshData};
+use polygon::math::*;
+use scene::Morph;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Returns the morph's meshes blended towards each of its targets by the targets' current
+/// weights.
+pub fn blend(morph: &Morph) -> Vec<MeshData> {
+    let mut meshes = morph.meshes.clone();
+    for (target, &weight) in morph.targets.iter().zip(&morph.weights) {
+        if weight == 0.0 {
+            continue;
+        }
+
+        for (mesh, deltas) in meshes.iter_mut().zip(&target.deltas) {
+            for (vertex, delta) in mesh.vertices.iter_mut().zip(deltas) {
+                vertex.position = Point::new(
+                    vertex.position.x + delta.position.x * weight,
+                    vertex.position.y + delta.position.y * weight,
+                    vertex.position.z + delta.position.z * weight,
+                );
+                vertex.normal = vertex.normal.map(|normal| Vector3 {
+                    x: normal.x + delta.normal.x * weight,
+                    y: normal.y + delta.normal.y * weight,
+                    z: normal.z + delta.normal.z * weight,
+                });
+            }
+        }
+    }
+
+    // The normals are only normalized at the end, so that blending isn't affected by the order
+    // of the targets.
+    for vertex in meshes.iter_mut().flat_map(|mesh| mesh.vertices.iter_mut()) {
+        vertex.normal = vertex.normal.map(normalize);
+    }
+
+    meshes
+}
+
+/// Weights picked by the user, keyed by the index of the morph and the index of the target.
+///
+/// Overrides are applied after the scene is animated, so that they take priority over any
+/// animation of the weights.
+pub type WeightOverrides = HashMap<(usize, usize), f32>;
+
+/// Returns the index of the morph's target identified by `target`, which is either the key of
+/// the target's geometry or the index of the target.
+pub fn find_target(morph: &Morph, target: &str) -> Option<usize> {
+    morph.targets.iter()
+        .position(|morph_target| morph_target.geometry.as_ref().map(String::as_str) == Some(target))
+        .or_else(|| target.parse().ok())
+        .and_then(|index| if index < morph.weights.len() { Some(index) } else { None })
+}
+
+/// Sets the weight of every overridden target.
+pub fn apply_overrides(morphs: &mut [Morph], overrides: &WeightOverrides) {
+    for (&(morph, target), &weight) in overrides {
+        if let Some(slot) = morphs.get_mut(morph).and_then(|morph| morph.weights.get_mut(target)) {
+            *slot = weight;
+        }
+    }
+}
+
+/// A morph target weight given on the command line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TargetWeight {
+    /// The key of the target's geometry, or the index of the target.
+    pub target: String,
+    pub weight: f32,
+}
+
+impl FromStr for TargetWeight {
+    type Err = String;
+
+    /// Parses a weight written as `target=weight`, e.g. `smile=0.5` or `0=1`.
+    fn from_str(string: &str) -> Result<TargetWeight, String> {
+        let mut parts = string.splitn(2, '=');
+        match (parts.next(), parts.next().and_then(|weight| weight.trim().parse().ok())) {
+            (Some(target), Some(weight)) => Ok(TargetWeight { target: target.trim().into(), weight }),
+            _ => Err(format!("Invalid morph target weight {:?}, expected target=weight", string)),
+        }
+    }
+}
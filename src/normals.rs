@@ -0,0 +1,131 @@
+//! Generating vertex normals for meshes that don't have them.
+
+use mesh::{normalize, MeshData};
+use polygon::math::*;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// How generated normals are shaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalMode {
+    /// Every triangle gets its own face normal, giving a faceted look.
+    Flat,
+
+    /// Normals are averaged across neighboring triangles, except across edges that are sharper
+    /// than the crease angle.
+    Smooth,
+}
+
+impl FromStr for NormalMode {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<NormalMode, String> {
+        match string {
+            "flat" => Ok(NormalMode::Flat),
+            "smooth" => Ok(NormalMode::Smooth),
+            _ => Err(format!("Unknown normal mode {:?}, expected one of flat, smooth", string)),
+        }
+    }
+}
+
+/// Replaces the normals of every vertex in the mesh with generated ones.
+///
+/// For smooth normals, each corner of a triangle gets the average of the face normals of the
+/// triangles that share the corner's position and whose face normals are within `crease_angle`
+/// degrees of the triangle's own, weighted by the triangles' areas. Triangles are matched by
+/// position rather than by vertex, so that normals are smoothed across vertices that were split
+/// for other attributes, e.g. along texture seams.
+///
+/// Vertices whose corners end up with different normals are split, so the mesh may gain
+/// vertices.
+pub fn generate(mesh: &mut MeshData, mode: NormalMode, crease_angle: f32) {
+    // The closures below borrow the mesh, so the new vertex data is built in its own scope.
+    let (vertices, indices) = {
+        let position_key = |vertex: u32| {
+            let position = mesh.vertices[vertex as usize].position;
+            [position.x.to_bits(), position.y.to_bits(), position.z.to_bits()]
+        };
+
+        // The length of each face normal is proportional to the area of the triangle, which is
+        // what weights the average.
+        let face_normals = mesh.indices.chunks(3)
+            .map(|triangle| {
+                let a = mesh.vertices[triangle[0] as usize].position;
+                let b = mesh.vertices[triangle[1] as usize].position;
+                let c = mesh.vertices[triangle[2] as usize].position;
+                cross(
+                    Vector3 { x: b.x - a.x, y: b.y - a.y, z: b.z - a.z },
+                    Vector3 { x: c.x - a.x, y: c.y - a.y, z: c.z - a.z },
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let mut position_faces = HashMap::<[u32; 3], Vec<usize>>::new();
+        for (face, triangle) in mesh.indices.chunks(3).enumerate() {
+            for &vertex in triangle {
+                let faces = position_faces.entry(position_key(vertex)).or_insert_with(Vec::new);
+                if !faces.contains(&face) {
+                    faces.push(face);
+                }
+            }
+        }
+
+        let min_cos = crease_angle.to_radians().cos();
+        let mut vertices = Vec::with_capacity(mesh.vertices.len());
+        let mut indices = Vec::with_capacity(mesh.indices.len());
+        let mut created = HashMap::<(u32, [u32; 3]), u32>::new();
+        for (face, triangle) in mesh.indices.chunks(3).enumerate() {
+            let face_normal = normalize(face_normals[face]);
+
+            for &vertex in triangle {
+                let normal = match mode {
+                    NormalMode::Flat => face_normal,
+
+                    NormalMode::Smooth => {
+                        let mut sum = Vector3 { x: 0.0, y: 0.0, z: 0.0 };
+                        for &other in &position_faces[&position_key(vertex)] {
+                            // Degenerate triangles have no direction of their own, so they're
+                            // smoothed with everything around them.
+                            let other_normal = face_normals[other];
+                            if is_zero(face_normal) || dot(face_normal, normalize(other_normal)) >= min_cos {
+                                sum = Vector3 { x: sum.x + other_normal.x, y: sum.y + other_normal.y, z: sum.z + other_normal.z };
+                            }
+                        }
+
+                        normalize(sum)
+                    }
+                };
+
+                let key = (vertex, [normal.x.to_bits(), normal.y.to_bits(), normal.z.to_bits()]);
+                let index = *created.entry(key).or_insert_with(|| {
+                    let mut new_vertex = mesh.vertices[vertex as usize].clone();
+                    new_vertex.normal = Some(normal);
+                    vertices.push(new_vertex);
+                    vertices.len() as u32 - 1
+                });
+                indices.push(index);
+            }
+        }
+
+        (vertices, indices)
+    };
+
+    mesh.vertices = vertices;
+    mesh.indices = indices;
+}
+
+fn cross(a: Vector3, b: Vector3) -> Vector3 {
+    Vector3 {
+        x: a.y * b.z - a.z * b.y,
+        y: a.z * b.x - a.x * b.z,
+        z: a.x * b.y - a.y * b.x,
+    }
+}
+
+fn dot(a: Vector3, b: Vector3) -> f32 {
+    a.x * b.x + a.y * b.y + a.z * b.z
+}
+
+fn is_zero(vector: Vector3) -> bool {
+    vector.x == 0.0 && vector.y == 0.0 && vector.z == 0.0
+}
@@ -0,0 +1,738 @@
+//! Loading Wavefront OBJ files and their MTL material libraries.
+//!
+//! Each object (`o`) or group (`g`) in the file is loaded as a separate geometry with its own
+//! root node, and the faces of each geometry are split into one mesh per material. OBJ files have
+//! no notion of up axis or units, so they're assumed to be Y-up and in meters unless the load
+//! options say otherwise.
+
+use loader::Loader;
+use mesh::{MeshData, Vertex};
+use normals::{self, NormalMode};
+use polygon::math::{Color, Point, Vector2, Vector3};
+use scene::{Geometry, GeometryInstance, LoadOptions, Material, Node, Scene, Shading, UpAxis};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use texture::{self, Texture};
+use triangulate;
+
+/// An error that occurred while loading an OBJ file.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The file couldn't be opened or read.
+    Io(io::Error),
+
+    /// The file doesn't contain any faces.
+    NoMeshes,
+
+    /// A statement had missing or malformed values, e.g. a `v` statement with only two
+    /// coordinates.
+    InvalidStatement {
+        line: usize,
+        statement: String,
+    },
+
+    /// A face referenced a vertex attribute that doesn't exist. `index` is the index as written
+    /// in the file, so it's 1-based or negative.
+    IndexOutOfRange {
+        line: usize,
+        attribute: &'static str,
+        index: i64,
+        count: usize,
+    },
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Io(ref error) => write!(f, "Failed to read file: {}", error),
+            LoadError::NoMeshes => write!(f, "No faces found in the file"),
+
+            LoadError::InvalidStatement { line, ref statement } => write!(
+                f,
+                "Invalid statement on line {}: {:?}",
+                line,
+                statement,
+            ),
+
+            LoadError::IndexOutOfRange { line, attribute, index, count } => write!(
+                f,
+                "Face on line {} references {} {}, but the file only has {} of them at that point",
+                line,
+                attribute,
+                index,
+                count,
+            ),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn description(&self) -> &str {
+        match *self {
+            LoadError::Io(..) => "Failed to read file",
+            LoadError::NoMeshes => "No faces found in the file",
+            LoadError::InvalidStatement { .. } => "Invalid statement",
+            LoadError::IndexOutOfRange { .. } => "Face references a missing vertex attribute",
+        }
+    }
+
+    fn cause(&self) -> Option<&dyn Error> {
+        match *self {
+            LoadError::Io(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(from: io::Error) -> LoadError {
+        LoadError::Io(from)
+    }
+}
+
+/// The OBJ loader, for the loader registry.
+pub struct ObjLoader;
+
+impl Loader for ObjLoader {
+    fn name(&self) -> &'static str {
+        "obj"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["obj"]
+    }
+
+    fn detect(&self, _header: &[u8]) -> bool {
+        // OBJ files have no header, so they're always picked by their extension.
+        false
+    }
+
+    fn load(&self, path: &Path, options: &LoadOptions) -> Result<Scene, Box<dyn Error>> {
+        load_scene(path, options).map_err(Into::into)
+    }
+}
+
+/// Loads the OBJ file at `path` as a scene, along with any MTL material libraries it uses.
+///
+/// Faces are triangulated as they're loaded, and negative indices are resolved relative to the
+/// end of the attribute lists at the point where the face appears. Material libraries that can't
+/// be found are skipped with a warning, leaving the faces that use them with the default
+/// material.
+pub fn load_scene<P: AsRef<Path>>(path: P, options: &LoadOptions) -> Result<Scene, LoadError> {
+    let path = path.as_ref();
+    let base = path.parent().unwrap_or(Path::new(""));
+    let reader = BufReader::new(File::open(path)?);
+
+    let mut parser = Parser {
+        options,
+        base,
+        positions: Vec::new(),
+        colors: Vec::new(),
+        texcoords: Vec::new(),
+        normals: Vec::new(),
+        groups: Vec::new(),
+        current_group: None,
+        pending_name: None,
+        current_material: None,
+        current_smoothing: 0,
+        uses_smoothing_groups: false,
+        materials: Vec::new(),
+        textures: TextureCache { base, textures: Vec::new(), loaded: HashMap::new() },
+        warned: HashSet::new(),
+    };
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        parser.parse_line(index + 1, &line)?;
+    }
+
+    parser.finish(path)
+}
+
+/// The state of an OBJ file as it's being parsed.
+struct Parser<'a> {
+    options: &'a LoadOptions,
+    base: &'a Path,
+
+    positions: Vec<Point>,
+
+    /// The color of each position, for files that use the common `v x y z r g b` extension.
+    colors: Vec<Option<Color>>,
+
+    texcoords: Vec<Vector2>,
+    normals: Vec<Vector3>,
+
+    groups: Vec<Group>,
+
+    /// The index of the group that faces are currently added to, or `None` if no faces have
+    /// been added since the last `o` or `g` statement.
+    current_group: Option<usize>,
+
+    /// The name of the group to create when the next face is added, if `current_group` is
+    /// `None`.
+    pending_name: Option<String>,
+
+    current_material: Option<String>,
+
+    /// The current smoothing group, where 0 means smoothing is off.
+    current_smoothing: u32,
+
+    /// Whether the file has any `s` statements. Files without them have normals generated
+    /// across all of a mesh's faces, rather than within each smoothing group.
+    uses_smoothing_groups: bool,
+
+    materials: Vec<Material>,
+    textures: TextureCache<'a>,
+
+    /// The statements that have already been warned about, so that each unsupported statement
+    /// is only reported once.
+    warned: HashSet<String>,
+}
+
+/// The faces of a single object or group, split up by material.
+struct Group {
+    name: Option<String>,
+    meshes: Vec<GroupMesh>,
+}
+
+/// The faces of a group that use a single material.
+struct GroupMesh {
+    data: MeshData,
+
+    /// The smoothing group of each triangle in the mesh.
+    smoothing: Vec<u32>,
+
+    /// Maps the position, texcoord, and normal indices of each corner that has been added to
+    /// the index of its vertex.
+    welded: HashMap<(usize, Option<usize>, Option<usize>), u32>,
+}
+
+impl<'a> Parser<'a> {
+    fn parse_line(&mut self, line: usize, text: &str) -> Result<(), LoadError> {
+        // Everything after a `#` is a comment.
+        let text = text.split('#').next().unwrap_or("").trim();
+        let mut words = text.split_whitespace();
+        let keyword = match words.next() {
+            Some(keyword) => keyword,
+            None => { return Ok(()); }
+        };
+        let invalid = || LoadError::InvalidStatement { line, statement: text.into() };
+
+        match keyword {
+            "v" => {
+                let values = parse_floats(words).ok_or_else(&invalid)?;
+                if values.len() < 3 {
+                    return Err(invalid());
+                }
+
+                // A fourth value is the homogeneous W coordinate, while six or seven values
+                // means the position is followed by a color.
+                self.positions.push(Point::new(values[0], values[1], values[2]));
+                self.colors.push(if values.len() >= 6 {
+                    Some(Color::rgb(values[3], values[4], values[5]))
+                } else {
+                    None
+                });
+            }
+
+            "vt" => {
+                let values = parse_floats(words).ok_or_else(&invalid)?;
+                if values.is_empty() {
+                    return Err(invalid());
+                }
+
+                self.texcoords.push(Vector2 { x: values[0], y: values.get(1).cloned().unwrap_or(0.0) });
+            }
+
+            "vn" => {
+                let values = parse_floats(words).ok_or_else(&invalid)?;
+                if values.len() < 3 {
+                    return Err(invalid());
+                }
+
+                self.normals.push(Vector3 { x: values[0], y: values[1], z: values[2] });
+            }
+
+            "f" => {
+                let mut corners = Vec::new();
+                for word in words {
+                    corners.push(self.parse_corner(line, text, word)?);
+                }
+
+                if corners.len() < 3 {
+                    self.warn_once("Faces with fewer than 3 corners can't be drawn, ignoring them".into());
+                } else {
+                    self.add_face(&corners);
+                }
+            }
+
+            // Objects and groups both start a new geometry. A group statement can list several
+            // group names, which are kept together as the geometry's name.
+            "o" | "g" => {
+                let name = words.collect::<Vec<_>>().join(" ");
+                self.pending_name = if name.is_empty() { None } else { Some(name) };
+                self.current_group = None;
+            }
+
+            "s" => {
+                self.uses_smoothing_groups = true;
+                self.current_smoothing = match words.next() {
+                    Some("off") | None => 0,
+                    Some(group) => group.parse().map_err(|_| invalid())?,
+                };
+            }
+
+            "usemtl" => {
+                let name = words.collect::<Vec<_>>().join(" ");
+                if !name.is_empty() && !self.materials.iter().any(|material| material.key() == Some(&*name)) {
+                    self.warn_once(format!("Material {:?} isn't defined in any material library", name));
+                }
+
+                self.current_material = if name.is_empty() { None } else { Some(name) };
+            }
+
+            "mtllib" => {
+                for file in words {
+                    let path = texture::resolve_uri(self.base, file);
+                    match load_materials(&path, &mut self.textures) {
+                        Ok(materials) => self.materials.extend(materials),
+                        Err(error) => {
+                            println!("WARNING: Failed to load material library {:?}: {}", path, error);
+                        }
+                    }
+                }
+            }
+
+            _ => {
+                self.warn_once(format!("{:?} statements aren't supported, ignoring them", keyword));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses a single corner of a face, written as `v`, `v/vt`, `v//vn`, or `v/vt/vn`.
+    fn parse_corner(&self, line: usize, statement: &str, word: &str) -> Result<Corner, LoadError> {
+        let invalid = || LoadError::InvalidStatement { line, statement: statement.into() };
+
+        // Parses one of the corner's indices and resolves it against the `count` elements of its
+        // attribute that have been read so far. Empty indices mean the attribute isn't used.
+        let resolve = |part: Option<&str>, attribute: &'static str, count: usize| -> Result<Option<usize>, LoadError> {
+            match part {
+                Some("") | None => Ok(None),
+                Some(part) => {
+                    let index = part.parse::<i64>().map_err(|_| invalid())?;
+                    resolve_index(index, count)
+                        .map(Some)
+                        .ok_or(LoadError::IndexOutOfRange { line, attribute, index, count })
+                }
+            }
+        };
+
+        let mut parts = word.split('/');
+        let position = resolve(parts.next(), "position", self.positions.len())?.ok_or_else(&invalid)?;
+        let texcoord = resolve(parts.next(), "texture coordinate", self.texcoords.len())?;
+        let normal = resolve(parts.next(), "normal", self.normals.len())?;
+        Ok(Corner { position, texcoord, normal })
+    }
+
+    /// Triangulates a face and adds it to the current group's mesh for the current material.
+    fn add_face(&mut self, corners: &[Corner]) {
+        let group_index = match self.current_group {
+            Some(index) => index,
+            None => {
+                // Objects and groups that are split up by other statements are merged back
+                // together.
+                let name = self.pending_name.clone();
+                let index = match self.groups.iter().position(|group| group.name == name) {
+                    Some(index) => index,
+                    None => {
+                        self.groups.push(Group { name, meshes: Vec::new() });
+                        self.groups.len() - 1
+                    }
+                };
+
+                self.current_group = Some(index);
+                index
+            }
+        };
+
+        let group = &mut self.groups[group_index];
+        let current_material = &self.current_material;
+        let mesh_index = match group.meshes.iter().position(|mesh| mesh.data.material == *current_material) {
+            Some(index) => index,
+            None => {
+                group.meshes.push(GroupMesh {
+                    data: MeshData { material: current_material.clone(), .. MeshData::default() },
+                    smoothing: Vec::new(),
+                    welded: HashMap::new(),
+                });
+                group.meshes.len() - 1
+            }
+        };
+        let mesh = &mut group.meshes[mesh_index];
+        let (positions, texcoords, normals) = (&self.positions, &self.texcoords, &self.normals);
+
+        let mut vertex_indices = Vec::with_capacity(corners.len());
+        for corner in corners {
+            let key = (corner.position, corner.texcoord, corner.normal);
+            if let Some(&index) = mesh.welded.get(&key) {
+                vertex_indices.push(index);
+                continue;
+            }
+
+            let mut vertex = Vertex::new(self.positions[corner.position]);
+            vertex.position_index = Some(corner.position);
+            vertex.color = self.colors[corner.position];
+            vertex.texcoord.extend(corner.texcoord.map(|index| texcoords[index]));
+            vertex.normal = corner.normal.map(|index| normals[index]);
+
+            let index = mesh.data.vertices.len() as u32;
+            mesh.data.vertices.push(vertex);
+            if self.options.weld_vertices {
+                mesh.welded.insert(key, index);
+            }
+            vertex_indices.push(index);
+        }
+
+        let points = corners.iter().map(|corner| positions[corner.position]).collect::<Vec<_>>();
+        for triangle in triangulate::triangulate(&points) {
+            mesh.data.indices.extend(triangle.iter().map(|&corner| vertex_indices[corner]));
+            mesh.smoothing.push(self.current_smoothing);
+        }
+    }
+
+    /// Builds the scene once every line of the file has been parsed.
+    fn finish(self, path: &Path) -> Result<Scene, LoadError> {
+        let mut scene = Scene::default();
+        scene.basis = self.options.up_axis.unwrap_or(UpAxis::Y).basis(self.options.meters_per_unit.unwrap_or(1.0));
+
+        // Faces that come before any object or group are named after the file.
+        let file_name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned());
+        for group in self.groups {
+            let mut meshes = Vec::with_capacity(group.meshes.len());
+            for group_mesh in group.meshes {
+                let GroupMesh { mut data, smoothing, .. } = group_mesh;
+                let needs_normals = self.options.regenerate_normals
+                    || data.vertices.iter().any(|vertex| vertex.normal.is_none());
+                if needs_normals {
+                    if self.uses_smoothing_groups {
+                        data = generate_by_smoothing_group(&data, &smoothing, self.options);
+                    } else {
+                        normals::generate(&mut data, self.options.normal_mode, self.options.crease_angle);
+                    }
+                }
+
+                data.transform(&scene.basis);
+                meshes.push(data);
+            }
+
+            let name = group.name.or_else(|| file_name.clone());
+            scene.geometries.push(Geometry { id: None, name, meshes });
+        }
+
+        if scene.geometries.is_empty() {
+            return Err(LoadError::NoMeshes);
+        }
+
+        // Material symbols in OBJ files are the materials' names, so every geometry is bound to
+        // every material.
+        let materials = self.materials.iter()
+            .enumerate()
+            .filter_map(|(index, material)| material.key().map(|key| (key.to_string(), index)))
+            .collect::<HashMap<_, _>>();
+        for (index, geometry) in scene.geometries.iter().enumerate() {
+            scene.nodes.push(Node {
+                name: geometry.name.clone(),
+                geometries: vec![GeometryInstance { geometry: index, materials: materials.clone() }],
+                .. Node::default()
+            });
+        }
+
+        scene.materials = self.materials;
+        scene.textures = self.textures.textures;
+        Ok(scene)
+    }
+
+    fn warn_once(&mut self, message: String) {
+        if self.warned.insert(message.clone()) {
+            println!("WARNING: {}", message);
+        }
+    }
+}
+
+/// The indices of the attributes of a single face corner, resolved into 0-based indices.
+#[derive(Debug, Clone, Copy)]
+struct Corner {
+    position: usize,
+    texcoord: Option<usize>,
+    normal: Option<usize>,
+}
+
+/// Converts an index as written in an OBJ file into a 0-based index into a list of `count`
+/// elements. Positive indices are 1-based, and negative indices count back from the end of the
+/// list, so -1 is the last element.
+fn resolve_index(index: i64, count: usize) -> Option<usize> {
+    let resolved = if index > 0 {
+        index - 1
+    } else {
+        count as i64 + index
+    };
+
+    if index != 0 && resolved >= 0 && (resolved as usize) < count {
+        Some(resolved as usize)
+    } else {
+        None
+    }
+}
+
+fn parse_floats<'b, I: Iterator<Item = &'b str>>(words: I) -> Option<Vec<f32>> {
+    words.map(|word| word.parse().ok()).collect()
+}
+
+/// Generates normals for a mesh according to the smoothing group of each of its triangles.
+///
+/// Triangles are only smoothed with the other triangles in their smoothing group, following
+/// `options.normal_mode` and `options.crease_angle` like the normals of any other mesh, while
+/// triangles with smoothing turned off always get flat normals.
+fn generate_by_smoothing_group(mesh: &MeshData, smoothing: &[u32], options: &LoadOptions) -> MeshData {
+    let mut groups = smoothing.to_vec();
+    groups.sort();
+    groups.dedup();
+
+    let mut result = MeshData { material: mesh.material.clone(), .. MeshData::default() };
+    for group in groups {
+        // Copy the group's triangles into their own mesh, so that they're only smoothed with
+        // each other.
+        let mut group_mesh = MeshData::default();
+        let mut remapped = HashMap::new();
+        for (triangle, _) in mesh.indices.chunks(3).zip(smoothing).filter(|&(_, &triangle_group)| triangle_group == group) {
+            for &vertex in triangle {
+                let index = *remapped.entry(vertex).or_insert_with(|| {
+                    group_mesh.vertices.push(mesh.vertices[vertex as usize].clone());
+                    group_mesh.vertices.len() as u32 - 1
+                });
+                group_mesh.indices.push(index);
+            }
+        }
+
+        let mode = if group == 0 { NormalMode::Flat } else { options.normal_mode };
+        normals::generate(&mut group_mesh, mode, options.crease_angle);
+
+        let offset = result.vertices.len() as u32;
+        result.vertices.extend(group_mesh.vertices);
+        result.indices.extend(group_mesh.indices.iter().map(|&index| index + offset));
+    }
+
+    result
+}
+
+/// Loads the materials in the MTL file at `path`.
+///
+/// Texture maps may be preceded by options such as `-bm 0.5`, which aren't supported, so the
+/// last word of a map statement is taken as the texture's file name.
+fn load_materials(path: &Path, textures: &mut TextureCache) -> io::Result<Vec<Material>> {
+    let reader = BufReader::new(File::open(path)?);
+
+    let mut materials: Vec<Material> = Vec::new();
+    let mut illumination = HashMap::new();
+    for line in reader.lines() {
+        let line = line?;
+        let text = line.split('#').next().unwrap_or("").trim();
+        let mut words = text.split_whitespace();
+        let keyword = match words.next() {
+            Some(keyword) => keyword,
+            None => { continue; }
+        };
+
+        if keyword == "newmtl" {
+            let name = words.collect::<Vec<_>>().join(" ");
+            materials.push(Material {
+                id: Some(name.clone()),
+                name: Some(name),
+                .. Material::default()
+            });
+            continue;
+        }
+
+        let index = materials.len();
+        let material = match materials.last_mut() {
+            Some(material) => material,
+            None => { continue; }
+        };
+
+        let values = words.clone().map(|word| word.parse::<f32>().ok()).collect::<Option<Vec<_>>>();
+        let color = || match values {
+            Some(ref values) if values.len() >= 3 => Some(Color::rgb(values[0], values[1], values[2])),
+            Some(ref values) if values.len() == 1 => Some(Color::rgb(values[0], values[0], values[0])),
+            _ => None,
+        };
+        let value = || values.as_ref().and_then(|values| values.first().cloned());
+
+        match keyword {
+            "Kd" => { material.diffuse = color().unwrap_or(material.diffuse); }
+            "Ks" => { material.specular = color().unwrap_or(material.specular); }
+            "Ka" => { material.ambient = color().unwrap_or(material.ambient); }
+            "Ke" => { material.emission = color().unwrap_or(material.emission); }
+            "Ns" => { material.shininess = value().unwrap_or(material.shininess); }
+            "d" => { material.transparency = value().map_or(material.transparency, |dissolve| 1.0 - dissolve); }
+            "Tr" => { material.transparency = value().unwrap_or(material.transparency); }
+            "illum" => {
+                if let Some(model) = value() {
+                    illumination.insert(index - 1, model as u32);
+                }
+            }
+
+            "map_Kd" => { material.diffuse_texture = words.last().map(|file| textures.load(file)); }
+            "map_Ks" => { material.specular_texture = words.last().map(|file| textures.load(file)); }
+            "map_Bump" | "map_bump" | "bump" | "norm" => {
+                material.normal_texture = words.last().map(|file| textures.load(file));
+            }
+
+            _ => {}
+        }
+    }
+
+    // Illumination model 0 is a constant color, 1 is diffuse only, and everything above that
+    // adds specular highlights.
+    for (index, model) in illumination {
+        let material = &mut materials[index];
+        match model {
+            0 => {
+                material.shading = Shading::Constant;
+                material.emission = material.diffuse;
+            }
+            1 => { material.shading = Shading::Lambert; }
+            _ => { material.shading = Shading::Phong; }
+        }
+    }
+
+    Ok(materials)
+}
+
+/// Loads the textures referenced by material libraries, loading each file only once.
+struct TextureCache<'a> {
+    base: &'a Path,
+    textures: Vec<Texture>,
+
+    /// Maps file names to indices in `textures`.
+    loaded: HashMap<String, usize>,
+}
+
+impl<'a> TextureCache<'a> {
+    /// Returns the index of the texture loaded from `file`, loading it if necessary.
+    fn load(&mut self, file: &str) -> usize {
+        if let Some(&index) = self.loaded.get(file) {
+            return index;
+        }
+
+        let index = self.textures.len();
+        self.textures.push(Texture::load(&texture::resolve_uri(self.base, file)));
+        self.loaded.insert(file.into(), index);
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("resources").join(name)
+    }
+
+    #[test]
+    fn resolve_index_handles_positive_and_negative_indices() {
+        assert_eq!(resolve_index(1, 3), Some(0));
+        assert_eq!(resolve_index(3, 3), Some(2));
+        assert_eq!(resolve_index(-1, 3), Some(2));
+        assert_eq!(resolve_index(-3, 3), Some(0));
+        assert_eq!(resolve_index(0, 3), None);
+        assert_eq!(resolve_index(4, 3), None);
+        assert_eq!(resolve_index(-4, 3), None);
+    }
+
+    #[test]
+    fn load_scene_resolves_negative_indices() {
+        let scene = load_scene(fixture("cube.obj"), &LoadOptions::default()).unwrap();
+        let names = scene.geometries.iter()
+            .map(|geometry| geometry.name.as_ref().map(String::as_str))
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec![Some("sides"), Some("caps")]);
+        assert_eq!(scene.materials.len(), 2);
+
+        // The caps' faces count back from the eighth position, which makes them the top and
+        // bottom of the cube. Smoothing is off for them, so they get flat normals.
+        let caps = &scene.geometries[1].meshes;
+        assert_eq!(caps.len(), 1);
+        assert_eq!(caps[0].material.as_ref().map(String::as_str), Some("red"));
+        assert_eq!(caps[0].indices.len(), 12);
+        for vertex in &caps[0].vertices {
+            assert_eq!(vertex.position.y.abs(), 0.5);
+            let normal = vertex.normal.unwrap();
+            assert_eq!((normal.x, normal.y, normal.z), (0.0, vertex.position.y * 2.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn smoothing_groups_use_the_crease_angle() {
+        // The sides share a smoothing group, but they meet at right angles, so the default
+        // crease angle keeps them faceted.
+        let scene = load_scene(fixture("cube.obj"), &LoadOptions::default()).unwrap();
+        let sides = &scene.geometries[0].meshes[0];
+        assert_eq!(sides.material.as_ref().map(String::as_str), Some("checker"));
+        assert_eq!(sides.indices.len(), 24);
+        for vertex in &sides.vertices {
+            let normal = vertex.normal.unwrap();
+            assert_eq!(normal.y, 0.0);
+            assert!(normal.x == 0.0 || normal.z == 0.0);
+            assert_eq!(vertex.texcoord.len(), 1);
+        }
+
+        // A wider crease angle smooths the sides across the corners of the cube.
+        let options = LoadOptions { crease_angle: 180.0, .. LoadOptions::default() };
+        let scene = load_scene(fixture("cube.obj"), &options).unwrap();
+        for vertex in &scene.geometries[0].meshes[0].vertices {
+            let normal = vertex.normal.unwrap();
+            assert_eq!(normal.y, 0.0);
+            assert!(normal.x != 0.0 && normal.z != 0.0);
+        }
+
+        // The caps have smoothing turned off, so they stay flat regardless.
+        for vertex in &scene.geometries[1].meshes[0].vertices {
+            let normal = vertex.normal.unwrap();
+            assert_eq!((normal.x, normal.y, normal.z), (0.0, vertex.position.y * 2.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn load_scene_triangulates_polygons() {
+        let scene = load_scene(fixture("pentagon.obj"), &LoadOptions::default()).unwrap();
+        assert_eq!(scene.geometries.len(), 1);
+        assert_eq!(scene.geometries[0].name.as_ref().map(String::as_str), Some("pentagon"));
+
+        // The pentagon is split into three triangles, and the face with only two corners is
+        // skipped.
+        let mesh = &scene.geometries[0].meshes[0];
+        assert_eq!(mesh.vertices.len(), 5);
+        assert_eq!(mesh.indices.len(), 9);
+
+        // Every triangle keeps the pentagon's counter-clockwise winding, and the normals from the
+        // file are kept.
+        for triangle in mesh.indices.chunks(3) {
+            let a = mesh.vertices[triangle[0] as usize].position;
+            let b = mesh.vertices[triangle[1] as usize].position;
+            let c = mesh.vertices[triangle[2] as usize].position;
+            assert!((b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x) > 0.0);
+        }
+        for vertex in &mesh.vertices {
+            let normal = vertex.normal.unwrap();
+            assert_eq!((normal.x, normal.y, normal.z), (0.0, 0.0, 1.0));
+        }
+    }
+}
@@ -0,0 +1,778 @@
+//! Loading PLY files in ASCII, binary little-endian, and binary big-endian formats.
+//!
+//! The header declares the file's elements and their properties, so the body is read according to
+//! the header and the well-known vertex properties are mapped onto the vertex data: `x`/`y`/`z`,
+//! `nx`/`ny`/`nz`, `s`/`t` or `u`/`v` texture coordinates, and `red`/`green`/`blue`/`alpha`
+//! colors. Any other properties and elements are skipped.
+//!
+//! Scans often have no faces at all, so files without faces are shown as a point cloud, with a
+//! small octahedron at each vertex.
+
+use loader::Loader;
+use mesh::{MeshData, Vertex};
+use normals;
+use polygon::math::{Color, Point, Vector2, Vector3};
+use scene::{Geometry, GeometryInstance, LoadOptions, Node, Scene, UpAxis};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::str;
+use triangulate;
+
+/// An error that occurred while loading a PLY file.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The file couldn't be opened or read.
+    Io(io::Error),
+
+    /// The header is malformed, e.g. it has a property of an unknown type or doesn't end with
+    /// `end_header`.
+    InvalidHeader {
+        line: usize,
+        reason: &'static str,
+    },
+
+    /// The body of the file doesn't match its header, e.g. it ends before every element has
+    /// been read.
+    InvalidData {
+        element: String,
+        index: usize,
+        reason: &'static str,
+    },
+
+    /// The vertex element doesn't have `x`, `y`, and `z` properties.
+    MissingPositions,
+
+    /// The file doesn't contain any vertices.
+    NoMeshes,
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Io(ref error) => write!(f, "Failed to read file: {}", error),
+
+            LoadError::InvalidHeader { line, reason } => write!(
+                f,
+                "Invalid PLY header on line {}: {}",
+                line,
+                reason,
+            ),
+
+            LoadError::InvalidData { ref element, index, reason } => write!(
+                f,
+                "Invalid data for {} {}: {}",
+                element,
+                index,
+                reason,
+            ),
+
+            LoadError::MissingPositions => write!(f, "The vertex element doesn't have x, y, and z properties"),
+            LoadError::NoMeshes => write!(f, "No vertices found in the file"),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn description(&self) -> &str {
+        match *self {
+            LoadError::Io(..) => "Failed to read file",
+            LoadError::InvalidHeader { .. } => "Invalid PLY header",
+            LoadError::InvalidData { .. } => "Data doesn't match the PLY header",
+            LoadError::MissingPositions => "Vertices have no positions",
+            LoadError::NoMeshes => "No vertices found in the file",
+        }
+    }
+
+    fn cause(&self) -> Option<&dyn Error> {
+        match *self {
+            LoadError::Io(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(from: io::Error) -> LoadError {
+        LoadError::Io(from)
+    }
+}
+
+/// The size of each octahedron in a point cloud, as a fraction of the diagonal of the cloud's
+/// bounding box.
+const POINT_SIZE: f32 = 0.005;
+
+/// The PLY loader, for the loader registry.
+pub struct PlyLoader;
+
+impl Loader for PlyLoader {
+    fn name(&self) -> &'static str {
+        "ply"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["ply"]
+    }
+
+    fn detect(&self, header: &[u8]) -> bool {
+        header.starts_with(b"ply\n") || header.starts_with(b"ply\r\n")
+    }
+
+    fn load(&self, path: &Path, options: &LoadOptions) -> Result<Scene, Box<dyn Error>> {
+        load_scene(path, options).map_err(Into::into)
+    }
+}
+
+/// Loads the PLY file at `path` as a scene with a single geometry.
+///
+/// PLY files have no notion of up axis or units, so they're assumed to be Y-up and in meters
+/// unless the load options say otherwise.
+pub fn load_scene<P: AsRef<Path>>(path: P, options: &LoadOptions) -> Result<Scene, LoadError> {
+    let path = path.as_ref();
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+
+    let (header, body) = read_header(&bytes)?;
+    let body = read_body(&header, body)?;
+    if body.vertices.is_empty() {
+        return Err(LoadError::NoMeshes);
+    }
+
+    let mut mesh = if body.face_sizes.is_empty() {
+        point_cloud(&body.vertices)
+    } else {
+        let mut mesh = build_faces(body)?;
+        if options.regenerate_normals || mesh.vertices.iter().any(|vertex| vertex.normal.is_none()) {
+            normals::generate(&mut mesh, options.normal_mode, options.crease_angle);
+        }
+        mesh
+    };
+
+    let mut scene = Scene::default();
+    scene.basis = options.up_axis.unwrap_or(UpAxis::Y).basis(options.meters_per_unit.unwrap_or(1.0));
+    mesh.transform(&scene.basis);
+
+    let name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned());
+    scene.geometries.push(Geometry { id: None, name: name.clone(), meshes: vec![mesh] });
+    scene.nodes.push(Node {
+        name,
+        geometries: vec![GeometryInstance { geometry: 0, materials: HashMap::new() }],
+        .. Node::default()
+    });
+
+    Ok(scene)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug)]
+struct Header {
+    format: Format,
+    elements: Vec<ElementDefinition>,
+}
+
+#[derive(Debug)]
+struct ElementDefinition {
+    name: String,
+    count: usize,
+    properties: Vec<PropertyDefinition>,
+}
+
+impl ElementDefinition {
+    /// Returns the index of the property named `name`, if the element has one.
+    fn property(&self, name: &str) -> Option<usize> {
+        self.properties.iter().position(|property| property.name == name)
+    }
+}
+
+#[derive(Debug)]
+struct PropertyDefinition {
+    name: String,
+    kind: PropertyKind,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum PropertyKind {
+    Scalar(ScalarType),
+
+    /// A list, stored as a count of the first type followed by that many items of the second
+    /// type.
+    List(ScalarType, ScalarType),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScalarType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl ScalarType {
+    /// Parses a type name, accepting both the original names (e.g. `uchar`) and the sized names
+    /// (e.g. `uint8`).
+    fn parse(name: &str) -> Option<ScalarType> {
+        match name {
+            "char" | "int8" => Some(ScalarType::Int8),
+            "uchar" | "uint8" => Some(ScalarType::UInt8),
+            "short" | "int16" => Some(ScalarType::Int16),
+            "ushort" | "uint16" => Some(ScalarType::UInt16),
+            "int" | "int32" => Some(ScalarType::Int32),
+            "uint" | "uint32" => Some(ScalarType::UInt32),
+            "float" | "float32" => Some(ScalarType::Float32),
+            "double" | "float64" => Some(ScalarType::Float64),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            ScalarType::Int8 | ScalarType::UInt8 => 1,
+            ScalarType::Int16 | ScalarType::UInt16 => 2,
+            ScalarType::Int32 | ScalarType::UInt32 | ScalarType::Float32 => 4,
+            ScalarType::Float64 => 8,
+        }
+    }
+
+    /// Returns the value that integer colors of this type are divided by to map them onto the
+    /// [0, 1] range. Floating point colors are already in that range.
+    fn color_scale(self) -> f64 {
+        match self {
+            ScalarType::Int8 => 127.0,
+            ScalarType::UInt8 => 255.0,
+            ScalarType::Int16 => 32767.0,
+            ScalarType::UInt16 => 65535.0,
+            ScalarType::Int32 => 2147483647.0,
+            ScalarType::UInt32 => 4294967295.0,
+            ScalarType::Float32 | ScalarType::Float64 => 1.0,
+        }
+    }
+}
+
+/// Parses the header, returning it along with the rest of the file.
+fn read_header(bytes: &[u8]) -> Result<(Header, &[u8]), LoadError> {
+    let mut format = None;
+    let mut elements: Vec<ElementDefinition> = Vec::new();
+    let mut offset = 0;
+    let mut line_number = 0;
+    loop {
+        line_number += 1;
+        let invalid = |reason| LoadError::InvalidHeader { line: line_number, reason };
+
+        let end = bytes[offset..].iter()
+            .position(|&byte| byte == b'\n')
+            .map(|end| offset + end)
+            .ok_or_else(|| invalid("the header doesn't end with end_header"))?;
+        let line = str::from_utf8(&bytes[offset..end]).map_err(|_| invalid("the header isn't ASCII"))?;
+        offset = end + 1;
+
+        let mut words = line.split_whitespace();
+        let keyword = words.next();
+        if line_number == 1 {
+            if keyword != Some("ply") {
+                return Err(invalid("the file doesn't start with \"ply\""));
+            }
+            continue;
+        }
+
+        match keyword {
+            Some("format") => {
+                format = Some(match words.next() {
+                    Some("ascii") => Format::Ascii,
+                    Some("binary_little_endian") => Format::BinaryLittleEndian,
+                    Some("binary_big_endian") => Format::BinaryBigEndian,
+                    _ => { return Err(invalid("unknown format")); }
+                });
+            }
+
+            Some("element") => {
+                let name = words.next().ok_or_else(|| invalid("element has no name"))?;
+                let count = words.next()
+                    .and_then(|count| count.parse().ok())
+                    .ok_or_else(|| invalid("element has no count"))?;
+                elements.push(ElementDefinition { name: name.into(), count, properties: Vec::new() });
+            }
+
+            Some("property") => {
+                let element = elements.last_mut().ok_or_else(|| invalid("property comes before any element"))?;
+                let kind = match words.next() {
+                    Some("list") => {
+                        let count_type = words.next().and_then(ScalarType::parse);
+                        let item_type = words.next().and_then(ScalarType::parse);
+                        match (count_type, item_type) {
+                            (Some(count_type), Some(item_type)) => PropertyKind::List(count_type, item_type),
+                            _ => { return Err(invalid("list property has an unknown type")); }
+                        }
+                    }
+
+                    Some(scalar) => {
+                        PropertyKind::Scalar(ScalarType::parse(scalar).ok_or_else(|| invalid("property has an unknown type"))?)
+                    }
+
+                    None => { return Err(invalid("property has no type")); }
+                };
+
+                let name = words.next().ok_or_else(|| invalid("property has no name"))?;
+                element.properties.push(PropertyDefinition { name: name.into(), kind });
+            }
+
+            Some("end_header") => { break; }
+            Some("comment") | Some("obj_info") | None => {}
+            Some(_) => { return Err(invalid("unknown keyword")); }
+        }
+    }
+
+    let format = format.ok_or(LoadError::InvalidHeader { line: line_number, reason: "the header has no format" })?;
+    Ok((Header { format, elements }, &bytes[offset..]))
+}
+
+/// The vertices and faces read from the body of a file.
+struct Body {
+    vertices: Vec<Vertex>,
+
+    /// The vertex indices of every face's corners, one face after another.
+    corners: Vec<u32>,
+
+    /// The number of corners of each face.
+    face_sizes: Vec<u32>,
+}
+
+/// Reads the vertex and face elements declared in the header, skipping any other elements.
+///
+/// Values are read straight into the vertex and index buffers, and buffers are only
+/// pre-allocated for as many instances as the rest of the file could hold, so that a header with
+/// bogus counts produces an error rather than an enormous allocation.
+fn read_body(header: &Header, body: &[u8]) -> Result<Body, LoadError> {
+    let mut reader = Reader { bytes: body, offset: 0, format: header.format };
+    let mut result = Body { vertices: Vec::new(), corners: Vec::new(), face_sizes: Vec::new() };
+
+    // The value of each property of the current instance. List properties store their first
+    // item, or zero if they're empty.
+    let mut values = Vec::new();
+    for element in &header.elements {
+        let capacity = element.count.min(reader.remaining() / reader.min_size(element).max(1));
+        let invalid = |index, reason| LoadError::InvalidData { element: element.name.clone(), index, reason };
+
+        match &*element.name {
+            "vertex" => {
+                let layout = VertexLayout::new(element)?;
+                result.vertices.reserve(capacity);
+                for index in 0..element.count {
+                    values.clear();
+                    for property in &element.properties {
+                        let mut first = None;
+                        reader.read_property(property.kind, |value| { first = first.or(Some(value)); })
+                            .map_err(|reason| invalid(index, reason))?;
+                        values.push(first.unwrap_or(0.0));
+                    }
+
+                    result.vertices.push(layout.build(&values, index));
+                }
+            }
+
+            "face" => {
+                let indices_property = element.property("vertex_indices")
+                    .or_else(|| element.property("vertex_index"));
+                if indices_property.is_none() && element.count > 0 {
+                    return Err(invalid(0, "faces have no vertex_indices property"));
+                }
+
+                result.face_sizes.reserve(capacity);
+                result.corners.reserve(capacity * 3);
+                for index in 0..element.count {
+                    for (property_index, property) in element.properties.iter().enumerate() {
+                        if Some(property_index) == indices_property {
+                            // Indices are read as floating point, so anything that isn't a
+                            // valid index has to be caught before it's converted.
+                            let corners = &mut result.corners;
+                            let mut valid = true;
+                            let size = reader.read_property(property.kind, |corner| {
+                                if corner >= 0.0 && corner.fract() == 0.0 && corner <= u32::max_value() as f64 {
+                                    corners.push(corner as u32);
+                                } else {
+                                    valid = false;
+                                }
+                            }).map_err(|reason| invalid(index, reason))?;
+                            if !valid {
+                                return Err(invalid(index, "a face has a negative, fractional, or too large vertex index"));
+                            }
+                            result.face_sizes.push(size as u32);
+                        } else {
+                            reader.read_property(property.kind, |_| {}).map_err(|reason| invalid(index, reason))?;
+                        }
+                    }
+                }
+            }
+
+            _ => {
+                for index in 0..element.count {
+                    for property in &element.properties {
+                        reader.read_property(property.kind, |_| {}).map_err(|reason| invalid(index, reason))?;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Reads scalar values from the body of a PLY file.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    format: Format,
+}
+
+impl<'a> Reader<'a> {
+    /// Returns the number of bytes left to read.
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.offset
+    }
+
+    /// Returns the fewest bytes that an instance of `element` can take up, assuming every list
+    /// is empty.
+    fn min_size(&self, element: &ElementDefinition) -> usize {
+        element.properties.iter()
+            .map(|property| match (self.format, property.kind) {
+                // Every ASCII value is at least one character followed by a separator.
+                (Format::Ascii, _) => 2,
+                (_, PropertyKind::Scalar(scalar)) => scalar.size(),
+                (_, PropertyKind::List(count_type, _)) => count_type.size(),
+            })
+            .sum()
+    }
+
+    /// Reads a property, passing each of its values to `value`. Returns the number of values
+    /// read, which is always 1 for scalar properties.
+    fn read_property<F: FnMut(f64)>(&mut self, kind: PropertyKind, mut value: F) -> Result<usize, &'static str> {
+        match kind {
+            PropertyKind::Scalar(scalar) => {
+                value(self.read(scalar).ok_or("the file ends too early or has a malformed value")?);
+                Ok(1)
+            }
+
+            PropertyKind::List(count_type, item_type) => {
+                let count = self.read(count_type).ok_or("the file ends too early or has a malformed value")?;
+                if count < 0.0 {
+                    return Err("a list has a negative length");
+                }
+
+                let count = count as usize;
+                let item_size = if self.format == Format::Ascii { 2 } else { item_type.size() };
+                if count.saturating_mul(item_size) > self.remaining() + 1 {
+                    return Err("a list is longer than the rest of the file");
+                }
+
+                for _ in 0..count {
+                    value(self.read(item_type).ok_or("the file ends too early or has a malformed value")?);
+                }
+                Ok(count)
+            }
+        }
+    }
+
+    /// Reads the next value, returning `None` at the end of the file or if an ASCII value is
+    /// malformed.
+    fn read(&mut self, scalar: ScalarType) -> Option<f64> {
+        if self.format == Format::Ascii {
+            let start = self.offset + self.bytes[self.offset..].iter()
+                .position(|byte| !byte.is_ascii_whitespace())?;
+            let end = self.bytes[start..].iter()
+                .position(|byte| byte.is_ascii_whitespace())
+                .map_or(self.bytes.len(), |end| start + end);
+            self.offset = end;
+            return str::from_utf8(&self.bytes[start..end]).ok()?.parse().ok();
+        }
+
+        let size = scalar.size();
+        let data = self.bytes.get(self.offset..self.offset + size)?;
+        self.offset += size;
+
+        let mut raw = [0u8; 8];
+        raw[..size].copy_from_slice(data);
+        if self.format == Format::BinaryBigEndian {
+            raw[..size].reverse();
+        }
+
+        // `raw` is now little-endian, padded with zeros.
+        let bits = raw.iter().rev().fold(0u64, |bits, &byte| bits << 8 | byte as u64);
+        Some(match scalar {
+            ScalarType::Int8 => bits as u8 as i8 as f64,
+            ScalarType::UInt8 => bits as u8 as f64,
+            ScalarType::Int16 => bits as u16 as i16 as f64,
+            ScalarType::UInt16 => bits as u16 as f64,
+            ScalarType::Int32 => bits as u32 as i32 as f64,
+            ScalarType::UInt32 => bits as u32 as f64,
+            ScalarType::Float32 => f32::from_bits(bits as u32) as f64,
+            ScalarType::Float64 => f64::from_bits(bits),
+        })
+    }
+}
+
+/// The indices of the vertex properties that are mapped onto the vertex data.
+struct VertexLayout {
+    position: [usize; 3],
+    normal: Option<[usize; 3]>,
+    texcoord: Option<[usize; 2]>,
+    color: Option<[usize; 3]>,
+    alpha: Option<usize>,
+
+    /// The value that each property is divided by when it's used as a color.
+    color_scales: Vec<f64>,
+}
+
+impl VertexLayout {
+    /// Finds the well-known properties of the vertex element.
+    fn new(definition: &ElementDefinition) -> Result<VertexLayout, LoadError> {
+        // Returns the index of the first of `names` that the element has, so that every naming
+        // convention in common use is recognized.
+        let find = |names: &[&str]| names.iter().filter_map(|name| definition.property(name)).next();
+
+        let position = match (find(&["x"]), find(&["y"]), find(&["z"])) {
+            (Some(x), Some(y), Some(z)) => [x, y, z],
+            _ => { return Err(LoadError::MissingPositions); }
+        };
+        let normal = match (find(&["nx"]), find(&["ny"]), find(&["nz"])) {
+            (Some(x), Some(y), Some(z)) => Some([x, y, z]),
+            _ => None,
+        };
+        let texcoord = match (find(&["s", "u", "texture_u"]), find(&["t", "v", "texture_v"])) {
+            (Some(s), Some(t)) => Some([s, t]),
+            _ => None,
+        };
+        let color = match (find(&["red", "r", "diffuse_red"]), find(&["green", "g", "diffuse_green"]), find(&["blue", "b", "diffuse_blue"])) {
+            (Some(r), Some(g), Some(b)) => Some([r, g, b]),
+            _ => None,
+        };
+        let alpha = find(&["alpha", "a", "diffuse_alpha"]);
+
+        // Integer colors are scaled into the [0, 1] range by the maximum value of their type.
+        let color_scales = definition.properties.iter()
+            .map(|property| match property.kind {
+                PropertyKind::Scalar(scalar) => scalar.color_scale(),
+                PropertyKind::List(..) => 1.0,
+            })
+            .collect();
+
+        Ok(VertexLayout { position, normal, texcoord, color, alpha, color_scales })
+    }
+
+    /// Builds the vertex at `index` from the values of its properties.
+    fn build(&self, values: &[f64], index: usize) -> Vertex {
+        let value = |property: usize| values[property] as f32;
+        let color_value = |property: usize| (values[property] / self.color_scales[property]) as f32;
+
+        let mut vertex = Vertex::new(Point::new(
+            value(self.position[0]),
+            value(self.position[1]),
+            value(self.position[2]),
+        ));
+        vertex.position_index = Some(index);
+        vertex.normal = self.normal.map(|[x, y, z]| Vector3 { x: value(x), y: value(y), z: value(z) });
+        vertex.texcoord.extend(self.texcoord.map(|[s, t]| Vector2 { x: value(s), y: value(t) }));
+        vertex.color = self.color.map(|[r, g, b]| Color::new(
+            color_value(r),
+            color_value(g),
+            color_value(b),
+            self.alpha.map_or(1.0, |a| color_value(a)),
+        ));
+        vertex
+    }
+}
+
+/// Builds a mesh from the vertices and faces, triangulating faces with more than three corners.
+fn build_faces(body: Body) -> Result<MeshData, LoadError> {
+    let Body { vertices, corners, face_sizes } = body;
+
+    let mut mesh = MeshData::default();
+    mesh.indices.reserve(corners.len());
+    let mut points = Vec::new();
+    let mut start = 0;
+    for (index, &size) in face_sizes.iter().enumerate() {
+        let face = &corners[start..start + size as usize];
+        start += size as usize;
+        if face.iter().any(|&corner| corner as usize >= vertices.len()) {
+            return Err(LoadError::InvalidData {
+                element: "face".into(),
+                index,
+                reason: "a face references a vertex that doesn't exist",
+            });
+        }
+
+        if face.len() == 3 {
+            mesh.indices.extend_from_slice(face);
+            continue;
+        }
+
+        points.clear();
+        points.extend(face.iter().map(|&corner| vertices[corner as usize].position));
+        for triangle in triangulate::triangulate(&points) {
+            mesh.indices.extend(triangle.iter().map(|&corner| face[corner]));
+        }
+    }
+
+    mesh.vertices = vertices;
+    Ok(mesh)
+}
+
+/// Builds a mesh with a small octahedron at each vertex, so that a cloud of points can be seen.
+///
+/// The octahedra are sized relative to the bounds of the cloud. Each octahedron's vertices copy
+/// the attributes of the point, except that their normals point outwards from the point so that
+/// the octahedra are shaded like small spheres.
+fn point_cloud(points: &[Vertex]) -> MeshData {
+    let (min, max) = points.iter().fold(
+        ([::std::f32::MAX; 3], [::std::f32::MIN; 3]),
+        |(min, max), point| {
+            let position = [point.position.x, point.position.y, point.position.z];
+            let mut min = min;
+            let mut max = max;
+            for axis in 0..3 {
+                min[axis] = min[axis].min(position[axis]);
+                max[axis] = max[axis].max(position[axis]);
+            }
+            (min, max)
+        },
+    );
+    let diagonal = ((max[0] - min[0]).powi(2) + (max[1] - min[1]).powi(2) + (max[2] - min[2]).powi(2)).sqrt();
+    let size = if diagonal > 0.0 { diagonal * POINT_SIZE } else { POINT_SIZE };
+
+    const DIRECTIONS: [[f32; 3]; 6] = [
+        [1.0, 0.0, 0.0],
+        [-1.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0],
+        [0.0, -1.0, 0.0],
+        [0.0, 0.0, 1.0],
+        [0.0, 0.0, -1.0],
+    ];
+
+    // The eight faces of the octahedron, as indices into `DIRECTIONS`, wound counter-clockwise
+    // when seen from outside.
+    const FACES: [[u32; 3]; 8] = [
+        [0, 2, 4],
+        [2, 1, 4],
+        [1, 3, 4],
+        [3, 0, 4],
+        [2, 0, 5],
+        [1, 2, 5],
+        [3, 1, 5],
+        [0, 3, 5],
+    ];
+
+    let mut mesh = MeshData::default();
+    mesh.vertices.reserve(points.len() * DIRECTIONS.len());
+    mesh.indices.reserve(points.len() * FACES.len() * 3);
+    for point in points {
+        let base = mesh.vertices.len() as u32;
+        for direction in &DIRECTIONS {
+            let mut vertex = point.clone();
+            vertex.position = Point::new(
+                point.position.x + direction[0] * size,
+                point.position.y + direction[1] * size,
+                point.position.z + direction[2] * size,
+            );
+            vertex.normal = Some(Vector3 { x: direction[0], y: direction[1], z: direction[2] });
+            vertex.position_index = None;
+            mesh.vertices.push(vertex);
+        }
+
+        for face in &FACES {
+            mesh.indices.extend(face.iter().map(|&corner| base + corner));
+        }
+    }
+
+    mesh
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("resources").join(name)
+    }
+
+    fn xyz(point: Point) -> (f32, f32, f32) {
+        (point.x, point.y, point.z)
+    }
+
+    #[test]
+    fn load_scene_reads_big_endian_files() {
+        let scene = load_scene(fixture("quad_big_endian.ply"), &LoadOptions::default()).unwrap();
+        assert_eq!(scene.geometries.len(), 1);
+        assert_eq!(scene.geometries[0].name.as_ref().map(String::as_str), Some("quad_big_endian"));
+
+        // The positions are doubles, the normals floats, the colors unsigned shorts, and the face
+        // indices ints, so every size of value is read with the right byte order.
+        let mesh = &scene.geometries[0].meshes[0];
+        let positions = mesh.vertices.iter().map(|vertex| xyz(vertex.position)).collect::<Vec<_>>();
+        assert_eq!(positions, vec![(0.0, 0.0, 0.0), (1.0, 0.0, 0.0), (1.0, 1.0, 0.0), (0.0, 1.0, 0.0)]);
+
+        let colors = mesh.vertices.iter()
+            .map(|vertex| vertex.color.map(|color| (color.r, color.g, color.b, color.a)))
+            .collect::<Vec<_>>();
+        assert_eq!(colors, vec![
+            Some((1.0, 0.0, 0.0, 1.0)),
+            Some((0.0, 1.0, 0.0, 1.0)),
+            Some((0.0, 0.0, 1.0, 1.0)),
+            Some((1.0, 1.0, 1.0, 1.0)),
+        ]);
+
+        for vertex in &mesh.vertices {
+            let normal = vertex.normal.unwrap();
+            assert_eq!((normal.x, normal.y, normal.z), (0.0, 0.0, 1.0));
+        }
+
+        // The quad is split into two triangles that keep its counter-clockwise winding.
+        assert_eq!(mesh.indices.len(), 6);
+        for triangle in mesh.indices.chunks(3) {
+            let a = mesh.vertices[triangle[0] as usize].position;
+            let b = mesh.vertices[triangle[1] as usize].position;
+            let c = mesh.vertices[triangle[2] as usize].position;
+            assert!((b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x) > 0.0);
+        }
+    }
+
+    #[test]
+    fn load_scene_draws_point_clouds_as_octahedra() {
+        let scene = load_scene(fixture("point_cloud.ply"), &LoadOptions::default()).unwrap();
+        let mesh = &scene.geometries[0].meshes[0];
+        assert_eq!(mesh.vertices.len(), 72 * 6);
+        assert_eq!(mesh.indices.len(), 72 * 8 * 3);
+
+        // The first point is at (0.2588, 0.9659, 0) with the color (160, 250, 127). Its
+        // octahedron is centered on it, and every corner has the point's color and a normal
+        // pointing away from it.
+        let octahedron = &mesh.vertices[..6];
+        let center = octahedron.iter().fold((0.0, 0.0, 0.0), |(x, y, z), vertex| {
+            (x + vertex.position.x / 6.0, y + vertex.position.y / 6.0, z + vertex.position.z / 6.0)
+        });
+        assert!((center.0 - 0.2588).abs() < 1e-6);
+        assert!((center.1 - 0.9659).abs() < 1e-6);
+        assert!(center.2.abs() < 1e-6);
+
+        for vertex in octahedron {
+            let color = vertex.color.unwrap();
+            let expected = |value: f64| (value / 255.0) as f32;
+            assert_eq!((color.r, color.g, color.b, color.a), (expected(160.0), expected(250.0), expected(127.0), 1.0));
+
+            let normal = vertex.normal.unwrap();
+            let offset = (vertex.position.x - 0.2588, vertex.position.y - 0.9659, vertex.position.z);
+            assert!(offset.0 * normal.x + offset.1 * normal.y + offset.2 * normal.z > 0.0);
+        }
+    }
+}
@@ -0,0 +1,684 @@
+//! Loader-agnostic scene description.
+//!
+//! A scene is a flattened node hierarchy along with the geometry that the nodes instance. Nodes
+//! are stored in a single list where every node comes after its parent, so world transforms can
+//! be computed in a single pass.
+
+use animation::{Channel, Target};
+use matrix::Matrix;
+use mesh::MeshData;
+use normals::NormalMode;
+use polygon::math::*;
+use std::collections::HashMap;
+use std::str::FromStr;
+use texture::Texture;
+
+/// A complete scene loaded from a mesh file.
+#[derive(Debug, Clone, Default)]
+pub struct Scene {
+    pub geometries: Vec<Geometry>,
+    pub materials: Vec<Material>,
+    pub textures: Vec<Texture>,
+    pub lights: Vec<Light>,
+    pub cameras: Vec<Camera>,
+    pub skins: Vec<Skin>,
+    pub morphs: Vec<Morph>,
+    pub nodes: Vec<Node>,
+
+    /// The animation channels that drive the scene's nodes.
+    pub channels: Vec<Channel>,
+
+    /// The transform from the file's coordinate system into the viewer's Y-up, meter-based
+    /// coordinate system.
+    ///
+    /// Geometry is converted when it's loaded, but node transforms are kept in the file's
+    /// coordinate system (so that animations can target them directly) and are only converted
+    /// when computing world transforms.
+    pub basis: Matrix,
+}
+
+impl Scene {
+    /// Returns the index of the geometry identified by `key`, matching against the geometries'
+    /// keys.
+    pub fn find_geometry(&self, key: &str) -> Option<usize> {
+        self.geometries.iter().position(|geometry| geometry.key() == Some(key))
+    }
+
+    /// Returns the index of the material identified by `key`, matching against the materials'
+    /// keys.
+    pub fn find_material(&self, key: &str) -> Option<usize> {
+        self.materials.iter().position(|material| material.key() == Some(key))
+    }
+
+    /// Returns the index of the light identified by `key`, matching against the lights' keys.
+    pub fn find_light(&self, key: &str) -> Option<usize> {
+        self.lights.iter().position(|light| light.key() == Some(key))
+    }
+
+    /// Returns the index of the camera identified by `key`, matching against the cameras' keys.
+    pub fn find_camera(&self, key: &str) -> Option<usize> {
+        self.cameras.iter().position(|camera| camera.key() == Some(key))
+    }
+
+    /// Returns the index of the skin identified by `key`, matching against the skins' keys.
+    pub fn find_skin(&self, key: &str) -> Option<usize> {
+        self.skins.iter().position(|skin| skin.key() == Some(key))
+    }
+
+    /// Returns the index of the morph identified by `key`, matching against the morphs' keys.
+    pub fn find_morph(&self, key: &str) -> Option<usize> {
+        self.morphs.iter().position(|morph| morph.key() == Some(key))
+    }
+
+    /// Returns every instance of a camera in the scene as a pair of node index and camera index,
+    /// in scene order. The first instance is the scene's active camera.
+    pub fn camera_instances(&self) -> Vec<(usize, usize)> {
+        self.nodes.iter()
+            .enumerate()
+            .flat_map(|(node_index, node)| node.cameras.iter().map(move |&camera| (node_index, camera)))
+            .collect()
+    }
+
+    /// Returns the time of the first and last keyframes in any of the scene's animation channels,
+    /// or `None` if the scene isn't animated.
+    pub fn animation_range(&self) -> Option<(f32, f32)> {
+        self.channels.iter()
+            .filter_map(Channel::range)
+            .fold(None, |range, (start, end)| match range {
+                Some((range_start, range_end)) => Some((f32::min(start, range_start), f32::max(end, range_end))),
+                None => Some((start, end)),
+            })
+    }
+
+    /// Poses the scene as it is at `time` seconds into its animation.
+    pub fn animate(&mut self, time: f32) {
+        for channel in &self.channels {
+            let values = channel.sample(time);
+            match channel.target {
+                Target::Transform { node, transform, component } => {
+                    let kind = &mut self.nodes[node].transforms[transform].kind;
+                    match component {
+                        Some(component) => {
+                            if let Some(&value) = values.first() {
+                                kind.set_value(component, value);
+                            }
+                        }
+
+                        None => {
+                            for (index, &value) in values.iter().enumerate() {
+                                kind.set_value(index, value);
+                            }
+                        }
+                    }
+                }
+
+                Target::MorphWeight { morph, target } => {
+                    let weight = self.morphs[morph].weights.get_mut(target);
+                    if let (Some(weight), Some(&value)) = (weight, values.first()) {
+                        *weight = value;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Computes the world transform of every node in the scene, in the same order as `nodes`.
+    ///
+    /// The transforms are converted into the viewer's coordinate system using `basis`.
+    pub fn world_transforms(&self) -> Vec<Matrix> {
+        let mut transforms: Vec<Matrix> = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let local = node.local_transform();
+            let world = match node.parent {
+                Some(parent) => transforms[parent] * local,
+                None => local,
+            };
+            transforms.push(world);
+        }
+
+        // Conjugating by the basis converts a transform from the file's coordinate system into
+        // one that operates on geometry that has already been converted to the viewer's.
+        let inverse_basis = self.basis.inverse().unwrap_or_else(Matrix::identity);
+        for transform in &mut transforms {
+            *transform = self.basis * *transform * inverse_basis;
+        }
+
+        transforms
+    }
+}
+
+/// Options that control how a mesh file is loaded.
+#[derive(Debug, Clone)]
+pub struct LoadOptions {
+    /// Whether corners that share the exact same indices for every input are merged into a
+    /// single vertex. Disabling this gives every corner of every face its own vertex, which is
+    /// occasionally useful when debugging the loader.
+    pub weld_vertices: bool,
+
+    /// Overrides the up axis declared in the file, if any.
+    pub up_axis: Option<UpAxis>,
+
+    /// Overrides the unit size (in meters) declared in the file, if any.
+    pub meters_per_unit: Option<f32>,
+
+    /// How normals are generated for meshes that don't have them.
+    pub normal_mode: NormalMode,
+
+    /// The angle in degrees between two faces above which smooth normals aren't averaged across
+    /// the faces' shared edge.
+    pub crease_angle: f32,
+
+    /// Whether normals are generated for every mesh, replacing the normals in the file.
+    /// Some exporters write broken normals, so this is useful for checking whether a shading
+    /// problem is caused by the file's normals.
+    pub regenerate_normals: bool,
+}
+
+impl Default for LoadOptions {
+    fn default() -> LoadOptions {
+        LoadOptions {
+            weld_vertices: true,
+            up_axis: None,
+            meters_per_unit: None,
+            normal_mode: NormalMode::Smooth,
+            crease_angle: 45.0,
+            regenerate_normals: false,
+        }
+    }
+}
+
+/// The meshes built from a single geometry in a mesh file.
+///
+/// A geometry may contain several primitive groups (e.g. one group per material), each of which
+/// is loaded as a separate mesh.
+#[derive(Debug, Clone)]
+pub struct Geometry {
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub meshes: Vec<MeshData>,
+}
+
+impl Geometry {
+    /// Returns the key that identifies the geometry, preferring its `id` over its `name`.
+    pub fn key(&self) -> Option<&str> {
+        self.id.as_ref().or(self.name.as_ref()).map(String::as_str)
+    }
+}
+
+/// A single node in the scene hierarchy.
+#[derive(Debug, Clone, Default)]
+pub struct Node {
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub sid: Option<String>,
+
+    /// The index of the node's parent in the scene's node list, or `None` for root nodes.
+    pub parent: Option<usize>,
+
+    /// The node's local transform, as a sequence of transforms that are applied in order, i.e.
+    /// the last transform in the list is applied to the node's contents first.
+    pub transforms: Vec<Transform>,
+
+    pub geometries: Vec<GeometryInstance>,
+
+    /// The indices of the lights attached to the node in the scene's light list.
+    pub lights: Vec<usize>,
+
+    /// The indices of the cameras attached to the node in the scene's camera list.
+    pub cameras: Vec<usize>,
+
+    pub skins: Vec<SkinInstance>,
+    pub morphs: Vec<MorphInstance>,
+}
+
+impl Node {
+    /// Composes the node's transforms into a single local transform matrix.
+    pub fn local_transform(&self) -> Matrix {
+        self.transforms.iter().fold(Matrix::identity(), |matrix, transform| matrix * transform.kind.matrix())
+    }
+}
+
+/// A single transform element of a node, e.g. one `<rotate>` element in a COLLADA node.
+#[derive(Debug, Clone)]
+pub struct Transform {
+    /// The scoped identifier of the transform, used to target it with animations.
+    pub sid: Option<String>,
+    pub kind: TransformKind,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum TransformKind {
+    Matrix(Matrix),
+
+    /// A translation along X, Y, and Z.
+    Translate([f32; 3]),
+
+    /// A rotation about the axis given by the first three values by the angle in degrees given
+    /// by the fourth value.
+    Rotate([f32; 4]),
+
+    /// A scale along X, Y, and Z.
+    Scale([f32; 3]),
+
+    /// The eye position, target position, and up vector of a look-at transform.
+    LookAt([f32; 9]),
+
+    /// The angle in degrees, rotation axis, and translation axis of a skew transform.
+    Skew([f32; 7]),
+}
+
+impl TransformKind {
+    /// Returns the number of values that make up the transform. Matrices have 16 values, in
+    /// row-major order.
+    pub fn value_count(&self) -> usize {
+        match *self {
+            TransformKind::Matrix(..) => 16,
+            TransformKind::Translate(..) | TransformKind::Scale(..) => 3,
+            TransformKind::Rotate(..) => 4,
+            TransformKind::LookAt(..) => 9,
+            TransformKind::Skew(..) => 7,
+        }
+    }
+
+    /// Sets the value at `index`, using the same order as the values of the transform's
+    /// variant. Indices past the end of the transform's values are ignored.
+    pub fn set_value(&mut self, index: usize, value: f32) {
+        let values: &mut [f32] = match *self {
+            TransformKind::Matrix(ref mut matrix) => {
+                if index < 16 {
+                    matrix.0[index / 4][index % 4] = value;
+                }
+                return;
+            }
+
+            TransformKind::Translate(ref mut values) => values,
+            TransformKind::Rotate(ref mut values) => values,
+            TransformKind::Scale(ref mut values) => values,
+            TransformKind::LookAt(ref mut values) => values,
+            TransformKind::Skew(ref mut values) => values,
+        };
+
+        if let Some(slot) = values.get_mut(index) {
+            *slot = value;
+        }
+    }
+
+    /// Converts the transform into a matrix.
+    pub fn matrix(&self) -> Matrix {
+        match *self {
+            TransformKind::Matrix(matrix) => matrix,
+            TransformKind::Translate([x, y, z]) => Matrix::translation(x, y, z),
+            TransformKind::Rotate([x, y, z, degrees]) => Matrix::rotation([x, y, z], degrees.to_radians()),
+            TransformKind::Scale([x, y, z]) => Matrix::scale(x, y, z),
+            TransformKind::LookAt(values) => Matrix::look_at(
+                [values[0], values[1], values[2]],
+                [values[3], values[4], values[5]],
+                [values[6], values[7], values[8]],
+            ),
+            TransformKind::Skew(values) => Matrix::skew(
+                values[0],
+                [values[1], values[2], values[3]],
+                [values[4], values[5], values[6]],
+            ),
+        }
+    }
+}
+
+/// An instance of a geometry attached to a node.
+#[derive(Debug, Clone)]
+pub struct GeometryInstance {
+    /// The index of the instanced geometry in the scene's geometry list.
+    pub geometry: usize,
+
+    /// Maps the material symbols used by the geometry's meshes to indices in the scene's
+    /// material list.
+    pub materials: HashMap<String, usize>,
+}
+
+impl GeometryInstance {
+    /// Returns the index of the material bound to `mesh`, if any.
+    pub fn material_for(&self, mesh: &MeshData) -> Option<usize> {
+        bound_material(&self.materials, mesh)
+    }
+}
+
+/// Returns the index of the material bound to `mesh`'s material symbol in `materials`, if any.
+fn bound_material(materials: &HashMap<String, usize>, mesh: &MeshData) -> Option<usize> {
+    mesh.material.as_ref().and_then(|symbol| materials.get(symbol)).cloned()
+}
+
+/// A mesh that is deformed by a skeleton of joint nodes.
+#[derive(Debug, Clone)]
+pub struct Skin {
+    pub id: Option<String>,
+    pub name: Option<String>,
+
+    /// The skinned meshes in their original shape, with the joint weights of each vertex filled
+    /// in.
+    pub meshes: Vec<MeshData>,
+
+    /// The transform applied to the meshes before skinning, which places them in the pose the
+    /// skeleton was bound in.
+    pub bind_shape_matrix: Matrix,
+
+    /// The names of the joints, which identify the joint nodes when the skin is instanced. The
+    /// joint indices in the meshes' vertices index into this list.
+    pub joints: Vec<String>,
+
+    /// The inverse of each joint's world transform at the time the skeleton was bound, in the
+    /// same order as `joints`.
+    pub inverse_bind_matrices: Vec<Matrix>,
+}
+
+impl Skin {
+    /// Returns the key that identifies the skin, preferring its `id` over its `name`.
+    pub fn key(&self) -> Option<&str> {
+        self.id.as_ref().or(self.name.as_ref()).map(String::as_str)
+    }
+}
+
+/// An instance of a skin attached to a node.
+///
+/// Skinned meshes are placed by their joints rather than by the node they're attached to.
+#[derive(Debug, Clone)]
+pub struct SkinInstance {
+    /// The index of the instanced skin in the scene's skin list.
+    pub skin: usize,
+
+    /// The index of the node for each of the skin's joints, in the same order as the skin's
+    /// `joints`.
+    pub joints: Vec<usize>,
+
+    /// Maps the material symbols used by the skin's meshes to indices in the scene's material
+    /// list.
+    pub materials: HashMap<String, usize>,
+}
+
+impl SkinInstance {
+    /// Returns the index of the material bound to `mesh`, if any.
+    pub fn material_for(&self, mesh: &MeshData) -> Option<usize> {
+        bound_material(&self.materials, mesh)
+    }
+}
+
+/// A mesh that blends between its base shape and a set of target shapes.
+#[derive(Debug, Clone)]
+pub struct Morph {
+    pub id: Option<String>,
+    pub name: Option<String>,
+
+    /// The meshes in their base shape.
+    pub meshes: Vec<MeshData>,
+
+    pub targets: Vec<MorphTarget>,
+
+    /// The current weight of each target, in the same order as `targets`.
+    pub weights: Vec<f32>,
+}
+
+impl Morph {
+    /// Returns the key that identifies the morph, preferring its `id` over its `name`.
+    pub fn key(&self) -> Option<&str> {
+        self.id.as_ref().or(self.name.as_ref()).map(String::as_str)
+    }
+}
+
+/// One of the shapes that a morph blends towards, stored as offsets from the morph's base shape.
+#[derive(Debug, Clone)]
+pub struct MorphTarget {
+    /// The key of the geometry that the target was loaded from.
+    pub geometry: Option<String>,
+
+    /// The offsets of each vertex of each of the morph's meshes, so that `deltas[i][j]` is the
+    /// offset of the `j`th vertex of the `i`th mesh.
+    pub deltas: Vec<Vec<MorphDelta>>,
+}
+
+/// The offset of a single vertex in a morph target.
+#[derive(Debug, Clone, Copy)]
+pub struct MorphDelta {
+    pub position: Vector3,
+    pub normal: Vector3,
+}
+
+/// An instance of a morph attached to a node.
+#[derive(Debug, Clone)]
+pub struct MorphInstance {
+    /// The index of the instanced morph in the scene's morph list.
+    pub morph: usize,
+
+    /// Maps the material symbols used by the morph's meshes to indices in the scene's material
+    /// list.
+    pub materials: HashMap<String, usize>,
+}
+
+impl MorphInstance {
+    /// Returns the index of the material bound to `mesh`, if any.
+    pub fn material_for(&self, mesh: &MeshData) -> Option<usize> {
+        bound_material(&self.materials, mesh)
+    }
+}
+
+/// A description of a surface material, following the common fixed-function lighting model.
+#[derive(Debug, Clone)]
+pub struct Material {
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub shading: Shading,
+    pub diffuse: Color,
+    pub specular: Color,
+    pub shininess: f32,
+    pub emission: Color,
+    pub ambient: Color,
+
+    /// How transparent the surface is, where 0 is fully opaque and 1 is fully transparent.
+    pub transparency: f32,
+
+    /// The indices of the material's textures in the scene's texture list.
+    pub diffuse_texture: Option<usize>,
+    pub specular_texture: Option<usize>,
+    pub normal_texture: Option<usize>,
+}
+
+impl Material {
+    /// Returns the key that identifies the material, preferring its `id` over its `name`.
+    pub fn key(&self) -> Option<&str> {
+        self.id.as_ref().or(self.name.as_ref()).map(String::as_str)
+    }
+}
+
+impl Default for Material {
+    fn default() -> Material {
+        Material {
+            id: None,
+            name: None,
+            shading: Shading::Phong,
+            diffuse: Color::rgb(1.0, 1.0, 1.0),
+            specular: Color::rgb(0.0, 0.0, 0.0),
+            shininess: 0.0,
+            emission: Color::rgb(0.0, 0.0, 0.0),
+            ambient: Color::rgb(0.0, 0.0, 0.0),
+            transparency: 0.0,
+            diffuse_texture: None,
+            specular_texture: None,
+            normal_texture: None,
+        }
+    }
+}
+
+/// The lighting model used to shade a material.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shading {
+    /// Only the emission color, with no lighting.
+    Constant,
+    Lambert,
+    Phong,
+    Blinn,
+}
+
+/// A light source. Lights shine along their node's local -Z axis.
+#[derive(Debug, Clone)]
+pub struct Light {
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub kind: LightKind,
+    pub color: Color,
+
+    /// The constant, linear, and quadratic attenuation factors of the light. The light's
+    /// intensity at distance `d` is scaled by `1 / (constant + linear * d + quadratic * d^2)`.
+    pub attenuation: [f32; 3],
+}
+
+impl Light {
+    /// Returns the key that identifies the light, preferring its `id` over its `name`.
+    pub fn key(&self) -> Option<&str> {
+        self.id.as_ref().or(self.name.as_ref()).map(String::as_str)
+    }
+
+    /// Returns the distance at which the light's attenuated intensity drops below `threshold`,
+    /// or `None` if the light isn't attenuated.
+    pub fn range(&self, threshold: f32) -> Option<f32> {
+        let [constant, linear, quadratic] = self.attenuation;
+        let target = 1.0 / threshold - constant;
+        if target <= 0.0 {
+            return Some(0.0);
+        }
+
+        if quadratic > 0.0 {
+            // Solve `quadratic * d^2 + linear * d - target = 0` for the positive root.
+            Some((-linear + (linear * linear + 4.0 * quadratic * target).sqrt()) / (2.0 * quadratic))
+        } else if linear > 0.0 {
+            Some(target / linear)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightKind {
+    Ambient,
+    Directional,
+    Point,
+
+    /// A spot light with a cone of `falloff_angle` degrees. Intensity falls off towards the
+    /// edge of the cone based on `falloff_exponent`.
+    Spot {
+        falloff_angle: f32,
+        falloff_exponent: f32,
+    },
+}
+
+/// A camera. Cameras look down their node's local -Z axis, with +Y up.
+#[derive(Debug, Clone)]
+pub struct Camera {
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub projection: Projection,
+    pub znear: f32,
+    pub zfar: f32,
+}
+
+impl Camera {
+    /// Returns the key that identifies the camera, preferring its `id` over its `name`.
+    pub fn key(&self) -> Option<&str> {
+        self.id.as_ref().or(self.name.as_ref()).map(String::as_str)
+    }
+}
+
+/// A camera projection. Files are allowed to specify any one or two of the horizontal size,
+/// vertical size, and aspect ratio, so each of them is optional.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// A perspective projection, with the field of view in degrees.
+    Perspective {
+        xfov: Option<f32>,
+        yfov: Option<f32>,
+        aspect_ratio: Option<f32>,
+    },
+
+    /// An orthographic projection, with the magnification (half of the view's size) in scene
+    /// units.
+    Orthographic {
+        xmag: Option<f32>,
+        ymag: Option<f32>,
+        aspect_ratio: Option<f32>,
+    },
+}
+
+impl Projection {
+    /// Resolves the vertical size of the projection given the aspect ratio of the viewport,
+    /// which is used if the projection doesn't specify enough information on its own.
+    ///
+    /// For perspective projections this is the vertical field of view in degrees, and for
+    /// orthographic projections it's the vertical magnification.
+    pub fn vertical_size(&self, viewport_aspect: f32) -> f32 {
+        let (x, y, aspect, is_perspective) = match *self {
+            Projection::Perspective { xfov, yfov, aspect_ratio } => (xfov, yfov, aspect_ratio, true),
+            Projection::Orthographic { xmag, ymag, aspect_ratio } => (xmag, ymag, aspect_ratio, false),
+        };
+
+        if let Some(y) = y {
+            return y;
+        }
+
+        let aspect = aspect.unwrap_or(viewport_aspect);
+        let x = x.unwrap_or(if is_perspective { 45.0 } else { 1.0 });
+        if is_perspective {
+            // Field of view doesn't scale linearly, so convert through the tangent of the
+            // half-angle.
+            2.0 * ((x / 2.0).to_radians().tan() / aspect).atan().to_degrees()
+        } else {
+            x / aspect
+        }
+    }
+}
+
+/// The axis that points up in a mesh file's coordinate system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpAxis {
+    X,
+    Y,
+    Z,
+}
+
+impl UpAxis {
+    /// Returns the transform that converts from a right-handed coordinate system with this up
+    /// axis and the given unit size into the viewer's Y-up, meter-based coordinate system.
+    pub fn basis(self, meters_per_unit: f32) -> Matrix {
+        let rotation = match self {
+            // X-up files have Y pointing left, which becomes -X.
+            UpAxis::X => Matrix([
+                [0.0, -1.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ]),
+
+            UpAxis::Y => Matrix::identity(),
+
+            // Z-up files have Y pointing away from the viewer, which becomes -Z.
+            UpAxis::Z => Matrix([
+                [1.0, 0.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+                [0.0, -1.0, 0.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ]),
+        };
+
+        Matrix::scale(meters_per_unit, meters_per_unit, meters_per_unit) * rotation
+    }
+}
+
+impl FromStr for UpAxis {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<UpAxis, String> {
+        match string {
+            "x" | "X" | "X_UP" => Ok(UpAxis::X),
+            "y" | "Y" | "Y_UP" => Ok(UpAxis::Y),
+            "z" | "Z" | "Z_UP" => Ok(UpAxis::Z),
+            _ => Err(format!("Unknown up axis {:?}, expected one of x, y, z", string)),
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! CPU skinning of meshes that are deformed by a skeleton.
+//!
+//! The renderer has no support for skinning, so skinned meshes are deformed on the CPU and
+//! uploaded as regular meshes. The deformed meshes are in world space, so they should be placed
+//! with an identity transform.
+
+use matrix::Matrix;
+use mesh::{normalize, MeshData};
+use scene::{Skin, SkinInstance};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Returns the skin's meshes in the pose that the skeleton was bound in.
+pub fn bind_pose(skin: &Skin) -> Vec<MeshData> {
+    skin.meshes.iter()
+        .map(|mesh| {
+            let mut mesh = mesh.clone();
+            mesh.transform(&skin.bind_shape_matrix);
+            mesh
+        })
+        .collect()
+}
+
+/// Returns the skin's meshes deformed by the current pose of the instance's joints.
+///
+/// `world_transforms` is the world transform of every node in the scene, as returned by
+/// `Scene::world_transforms()`.
+pub fn pose(skin: &Skin, instance: &SkinInstance, world_transforms: &[Matrix]) -> Vec<MeshData> {
+    // Each joint's matrix moves a vertex from the bind shape into the joint's space as it was
+    // bound, then back out using the joint's current transform.
+    let joint_matrices = instance.joints.iter()
+        .zip(&skin.inverse_bind_matrices)
+        .map(|(&node, &inverse_bind_matrix)| {
+            world_transforms[node] * inverse_bind_matrix * skin.bind_shape_matrix
+        })
+        .collect::<Vec<_>>();
+
+    // Mirroring joints flip the winding of the triangles they move, which is undone per triangle
+    // by the joint with the most influence over it, the same way `MeshData::transform` does for
+    // the whole mesh.
+    let mirrored = joint_matrices.iter().map(|matrix| matrix.determinant() < 0.0).collect::<Vec<_>>();
+    let unbound_mirrored = skin.bind_shape_matrix.determinant() < 0.0;
+
+    skin.meshes.iter()
+        .map(|mesh| {
+            // Vertices with the same influences share a blended matrix, so each blended matrix is
+            // only inverted once for transforming normals.
+            let mut blended = HashMap::<Vec<(usize, u32)>, (Matrix, Matrix)>::new();
+
+            let mut posed = mesh.clone();
+            for vertex in &mut posed.vertices {
+                let key = vertex.joints.iter().map(|&(joint, weight)| (joint, weight.to_bits())).collect::<Vec<_>>();
+                let (transform, normal_matrix) = *blended.entry(key).or_insert_with(|| {
+                    // Vertices without any influences aren't attached to the skeleton, so they
+                    // stay in the bind shape.
+                    let transform = if vertex.joints.is_empty() {
+                        skin.bind_shape_matrix
+                    } else {
+                        blend(&joint_matrices, &vertex.joints)
+                    };
+
+                    (transform, transform.normal_matrix())
+                });
+
+                vertex.position = transform.transform_point(vertex.position);
+                vertex.normal = vertex.normal.map(|normal| normalize(normal_matrix.transform_vector(normal)));
+                vertex.tangent = vertex.tangent.map(|tangent| normalize(transform.transform_vector(tangent)));
+                vertex.binormal = vertex.binormal.map(|binormal| normalize(transform.transform_vector(binormal)));
+            }
+
+            for triangle in posed.indices.chunks_mut(3) {
+                let is_mirrored = dominant_joint(mesh, triangle)
+                    .map_or(unbound_mirrored, |joint| mirrored[joint]);
+                if is_mirrored {
+                    triangle.swap(1, 2);
+                }
+            }
+
+            posed
+        })
+        .collect()
+}
+
+/// Returns the joint with the largest total weight over the corners of a triangle, or `None` if
+/// none of the corners are attached to the skeleton.
+fn dominant_joint(mesh: &MeshData, triangle: &[u32]) -> Option<usize> {
+    let mut weights = Vec::<(usize, f32)>::new();
+    for &vertex in triangle {
+        for &(joint, weight) in &mesh.vertices[vertex as usize].joints {
+            let existing = weights.iter().position(|&(other, _)| other == joint);
+            match existing {
+                Some(index) => weights[index].1 += weight,
+                None => weights.push((joint, weight)),
+            }
+        }
+    }
+
+    weights.iter()
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))
+        .map(|&(joint, _)| joint)
+}
+
+/// Blends the joint matrices by the weights of a vertex's influences.
+///
+/// Transforming a point by the blended matrix is the same as blending the point transformed by
+/// each of the matrices, which is the standard linear blend skinning.
+fn blend(joint_matrices: &[Matrix], influences: &[(usize, f32)]) -> Matrix {
+    let mut result = [[0.0; 4]; 4];
+    for &(joint, weight) in influences {
+        let matrix = &joint_matrices[joint].0;
+        for row in 0..4 {
+            for column in 0..4 {
+                result[row][column] += matrix[row][column] * weight;
+            }
+        }
+    }
+
+    Matrix(result)
+}
@@ -0,0 +1,458 @@
+//! Loading STL files, in both their ASCII and binary forms.
+//!
+//! STL files are a flat list of triangles, each with its own copy of its corner positions and a
+//! facet normal, so corners are welded back together into indexed vertices as they're loaded.
+//! STL has no notion of units or up axis, but nearly every file comes from a CAD or 3D printing
+//! tool, so files are assumed to be Z-up unless the load options say otherwise. Those tools
+//! usually work in millimeters, which can be selected with the `meters_per_unit` option.
+
+use loader::{self, Loader};
+use mesh::{normalize, MeshData, Vertex};
+use normals;
+use polygon::math::{Point, Vector3};
+use scene::{Geometry, GeometryInstance, LoadOptions, Node, Scene, UpAxis};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// An error that occurred while loading an STL file.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The file couldn't be opened or read.
+    Io(io::Error),
+
+    /// An ASCII file had an unexpected keyword or a malformed number.
+    InvalidAscii {
+        line: usize,
+        reason: &'static str,
+    },
+
+    /// A binary file is shorter than the number of triangles in its header requires.
+    TruncatedBinary {
+        triangles: usize,
+        length: usize,
+    },
+
+    /// The file doesn't contain any triangles.
+    NoMeshes,
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Io(ref error) => write!(f, "Failed to read file: {}", error),
+
+            LoadError::InvalidAscii { line, reason } => write!(
+                f,
+                "Invalid ASCII STL on line {}: {}",
+                line,
+                reason,
+            ),
+
+            LoadError::TruncatedBinary { triangles, length } => write!(
+                f,
+                "Binary STL header declares {} triangles, but the file is only {} bytes long",
+                triangles,
+                length,
+            ),
+
+            LoadError::NoMeshes => write!(f, "No triangles found in the file"),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn description(&self) -> &str {
+        match *self {
+            LoadError::Io(..) => "Failed to read file",
+            LoadError::InvalidAscii { .. } => "Invalid ASCII STL",
+            LoadError::TruncatedBinary { .. } => "Binary STL is truncated",
+            LoadError::NoMeshes => "No triangles found in the file",
+        }
+    }
+
+    fn cause(&self) -> Option<&dyn Error> {
+        match *self {
+            LoadError::Io(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(from: io::Error) -> LoadError {
+        LoadError::Io(from)
+    }
+}
+
+/// The size of a binary STL header, which is an 80 byte comment followed by the triangle count.
+const BINARY_HEADER_SIZE: usize = 84;
+
+/// The size of a single triangle in a binary STL file: the normal and three corners as 32-bit
+/// floats, followed by a 16-bit attribute.
+const BINARY_TRIANGLE_SIZE: usize = 50;
+
+/// The STL loader, for the loader registry.
+pub struct StlLoader;
+
+impl Loader for StlLoader {
+    fn name(&self) -> &'static str {
+        "stl"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["stl"]
+    }
+
+    fn detect(&self, header: &[u8]) -> bool {
+        // Only ASCII files have a recognizable header. Binary files that don't happen to start
+        // with "solid" are picked by their extension.
+        loader::trim_start(header).starts_with(b"solid")
+    }
+
+    fn load(&self, path: &Path, options: &LoadOptions) -> Result<Scene, Box<dyn Error>> {
+        load_scene(path, options).map_err(Into::into)
+    }
+}
+
+/// Loads the STL file at `path` as a scene.
+///
+/// Each solid in the file is loaded as a separate geometry with its own root node. Facet normals
+/// are used as flat normals unless they're zero, in which case they're computed from the
+/// triangle's winding. If normals are being regenerated, the facet normals are discarded and the
+/// welded vertices are shaded according to the load options instead.
+pub fn load_scene<P: AsRef<Path>>(path: P, options: &LoadOptions) -> Result<Scene, LoadError> {
+    let path = path.as_ref();
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+
+    let name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned());
+    let solids = if is_binary(&bytes) {
+        vec![read_binary(&bytes, name)?]
+    } else {
+        match read_ascii(&String::from_utf8_lossy(&bytes)) {
+            Ok(solids) => solids,
+
+            // A binary file can start with "solid" and still pass for text in its header, so
+            // a file that isn't valid ASCII is read as binary if it's long enough to be.
+            Err(error) => {
+                if !has_binary_length(&bytes) {
+                    return Err(error);
+                }
+
+                vec![read_binary(&bytes, name)?]
+            }
+        }
+    };
+
+    let mut scene = Scene::default();
+    scene.basis = options.up_axis.unwrap_or(UpAxis::Z).basis(options.meters_per_unit.unwrap_or(1.0));
+    for solid in solids {
+        if solid.facets.is_empty() {
+            continue;
+        }
+
+        let mut mesh = weld(&solid.facets, options);
+        if options.regenerate_normals {
+            normals::generate(&mut mesh, options.normal_mode, options.crease_angle);
+        }
+        mesh.transform(&scene.basis);
+
+        scene.nodes.push(Node {
+            name: solid.name.clone(),
+            geometries: vec![GeometryInstance { geometry: scene.geometries.len(), materials: HashMap::new() }],
+            .. Node::default()
+        });
+        scene.geometries.push(Geometry { id: None, name: solid.name, meshes: vec![mesh] });
+    }
+
+    if scene.geometries.is_empty() {
+        return Err(LoadError::NoMeshes);
+    }
+
+    Ok(scene)
+}
+
+/// A named list of triangles.
+struct Solid {
+    name: Option<String>,
+    facets: Vec<Facet>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Facet {
+    normal: Vector3,
+    corners: [Point; 3],
+}
+
+/// Returns whether `bytes` is a binary STL file.
+///
+/// ASCII files start with "solid", but so do the headers of some binary files, so a file is
+/// treated as binary if its length matches the triangle count in its binary header. Some
+/// exporters write trailing bytes after the triangles, so a longer file is also treated as binary
+/// if its header contains bytes that can't appear in an ASCII file.
+fn is_binary(bytes: &[u8]) -> bool {
+    if bytes.len() >= BINARY_HEADER_SIZE {
+        let count = read_u32(bytes, 80) as usize;
+        if count.saturating_mul(BINARY_TRIANGLE_SIZE) == bytes.len() - BINARY_HEADER_SIZE {
+            return true;
+        }
+
+        let is_text = |byte: &u8| byte.is_ascii_graphic() || byte.is_ascii_whitespace();
+        if has_binary_length(bytes) && !bytes[..BINARY_HEADER_SIZE].iter().all(is_text) {
+            return true;
+        }
+    }
+
+    let start = bytes.iter().position(|byte| !byte.is_ascii_whitespace()).unwrap_or(bytes.len());
+    !bytes[start..].starts_with(b"solid")
+}
+
+/// Returns whether `bytes` is at least as long as the triangle count in its binary header
+/// requires.
+fn has_binary_length(bytes: &[u8]) -> bool {
+    bytes.len() >= BINARY_HEADER_SIZE
+        && (read_u32(bytes, 80) as usize).saturating_mul(BINARY_TRIANGLE_SIZE) <= bytes.len() - BINARY_HEADER_SIZE
+}
+
+fn read_binary(bytes: &[u8], name: Option<String>) -> Result<Solid, LoadError> {
+    let triangles = if bytes.len() >= BINARY_HEADER_SIZE { read_u32(bytes, 80) as usize } else { 0 };
+    if bytes.len() < BINARY_HEADER_SIZE.saturating_add(triangles.saturating_mul(BINARY_TRIANGLE_SIZE)) {
+        return Err(LoadError::TruncatedBinary { triangles, length: bytes.len() });
+    }
+
+    let mut facets = Vec::with_capacity(triangles);
+    for triangle in bytes[BINARY_HEADER_SIZE..].chunks(BINARY_TRIANGLE_SIZE).take(triangles) {
+        let float = |index: usize| f32::from_bits(read_u32(triangle, index * 4));
+        let point = |index: usize| Point::new(float(index), float(index + 1), float(index + 2));
+        facets.push(Facet {
+            normal: Vector3 { x: float(0), y: float(1), z: float(2) },
+            corners: [point(3), point(6), point(9)],
+        });
+    }
+
+    Ok(Solid { name, facets })
+}
+
+/// Reads every solid in an ASCII STL file.
+fn read_ascii(text: &str) -> Result<Vec<Solid>, LoadError> {
+    let mut solids = Vec::new();
+    let mut solid: Option<Solid> = None;
+    let mut normal = Vector3 { x: 0.0, y: 0.0, z: 0.0 };
+    let mut corners = Vec::with_capacity(3);
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let invalid = |reason| LoadError::InvalidAscii { line: line_number, reason };
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("solid") => {
+                if solid.is_some() {
+                    return Err(invalid("\"solid\" inside another solid"));
+                }
+
+                let name = words.collect::<Vec<_>>().join(" ");
+                solid = Some(Solid { name: if name.is_empty() { None } else { Some(name) }, facets: Vec::new() });
+            }
+
+            Some("facet") => {
+                if words.next() != Some("normal") {
+                    return Err(invalid("expected \"normal\" after \"facet\""));
+                }
+
+                let [x, y, z] = read_triple(&mut words, line_number)?;
+                normal = Vector3 { x, y, z };
+                corners.clear();
+            }
+
+            Some("vertex") => {
+                let [x, y, z] = read_triple(&mut words, line_number)?;
+                corners.push(Point::new(x, y, z));
+            }
+
+            Some("endfacet") => {
+                if corners.len() != 3 {
+                    return Err(invalid("facet doesn't have exactly three vertices"));
+                }
+
+                let facet = Facet { normal, corners: [corners[0], corners[1], corners[2]] };
+                solid.as_mut().ok_or_else(|| invalid("facet outside of a solid"))?.facets.push(facet);
+            }
+
+            Some("endsolid") => {
+                solids.extend(solid.take());
+            }
+
+            Some("outer") | Some("endloop") | None => {}
+            Some(_) => { return Err(invalid("unknown keyword")); }
+        }
+    }
+
+    // Some exporters leave off the final "endsolid".
+    solids.extend(solid);
+    Ok(solids)
+}
+
+/// Reads the three coordinates that follow a `facet normal` or `vertex` keyword.
+fn read_triple<'a, I: Iterator<Item = &'a str>>(words: &mut I, line: usize) -> Result<[f32; 3], LoadError> {
+    let mut values = [0.0; 3];
+    for value in &mut values {
+        let word = words.next().ok_or(LoadError::InvalidAscii { line, reason: "missing coordinates" })?;
+        *value = word.parse().map_err(|_| LoadError::InvalidAscii { line, reason: "malformed number" })?;
+    }
+
+    Ok(values)
+}
+
+/// Builds an indexed mesh from a list of facets.
+///
+/// Corners that share a position and normal are welded into a single vertex. Facets with a zero
+/// normal get a normal computed from the winding of their corners. When normals are going to be
+/// regenerated, corners are welded by position alone and are left without normals.
+fn weld(facets: &[Facet], options: &LoadOptions) -> MeshData {
+    let mut mesh = MeshData::default();
+    let mut positions = HashMap::<[u32; 3], usize>::new();
+    let mut welded = HashMap::<([u32; 3], [u32; 3]), u32>::new();
+    for facet in facets {
+        let normal = if options.regenerate_normals {
+            None
+        } else if facet.normal.x == 0.0 && facet.normal.y == 0.0 && facet.normal.z == 0.0 {
+            Some(face_normal(&facet.corners))
+        } else {
+            Some(normalize(facet.normal))
+        };
+
+        for &corner in &facet.corners {
+            let position_key = [key(corner.x), key(corner.y), key(corner.z)];
+            let normal_key = normal.map_or([0; 3], |normal| [key(normal.x), key(normal.y), key(normal.z)]);
+            if let Some(&index) = welded.get(&(position_key, normal_key)) {
+                mesh.indices.push(index);
+                continue;
+            }
+
+            // Vertices that share a position share a position index, even if they have
+            // different normals, so that they're treated as one point when generating normals.
+            let position_count = positions.len();
+            let position_index = *positions.entry(position_key).or_insert(position_count);
+
+            let mut vertex = Vertex::new(corner);
+            vertex.normal = normal;
+            vertex.position_index = Some(position_index);
+
+            let index = mesh.vertices.len() as u32;
+            mesh.vertices.push(vertex);
+            if options.weld_vertices {
+                welded.insert((position_key, normal_key), index);
+            }
+            mesh.indices.push(index);
+        }
+    }
+
+    mesh
+}
+
+/// Returns the bits of `value` for use in a weld key, with -0.0 turned into 0.0 so that the two
+/// zeroes weld together.
+fn key(value: f32) -> u32 {
+    (value + 0.0).to_bits()
+}
+
+/// Computes the normal of a triangle from the counter-clockwise winding of its corners.
+fn face_normal(corners: &[Point; 3]) -> Vector3 {
+    let a = Vector3 { x: corners[1].x - corners[0].x, y: corners[1].y - corners[0].y, z: corners[1].z - corners[0].z };
+    let b = Vector3 { x: corners[2].x - corners[0].x, y: corners[2].y - corners[0].y, z: corners[2].z - corners[0].z };
+    normalize(Vector3 {
+        x: a.y * b.z - a.z * b.y,
+        y: a.z * b.x - a.x * b.z,
+        z: a.x * b.y - a.y * b.x,
+    })
+}
+
+/// Reads a little-endian `u32` at `offset`. The caller must make sure there are four bytes
+/// available.
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    bytes[offset] as u32
+        | (bytes[offset + 1] as u32) << 8
+        | (bytes[offset + 2] as u32) << 16
+        | (bytes[offset + 3] as u32) << 24
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("resources").join(name)
+    }
+
+    fn read_fixture(name: &str) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        File::open(fixture(name)).unwrap().read_to_end(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn is_binary_detects_binary_files_with_solid_headers() {
+        assert!(!is_binary(&read_fixture("tetrahedron.stl")));
+
+        // The binary file's header starts with "solid", but its length matches its triangle
+        // count.
+        let binary = read_fixture("tetrahedron_binary.stl");
+        assert!(binary.starts_with(b"solid"));
+        assert!(is_binary(&binary));
+
+        // Trailing bytes after the triangles are allowed, since the triangle count can't be text.
+        let mut trailing = binary.clone();
+        trailing.extend_from_slice(b"\0\0\0\0");
+        assert!(is_binary(&trailing));
+        assert_eq!(read_binary(&trailing, None).unwrap().facets.len(), 4);
+    }
+
+    #[test]
+    fn is_binary_handles_huge_triangle_counts() {
+        // A text file whose triangle count would overflow when multiplied out.
+        let mut bytes = b"solid huge".to_vec();
+        bytes.resize(BINARY_HEADER_SIZE, b' ');
+        bytes[80..84].copy_from_slice(&[0xff; 4]);
+        assert!(!is_binary(&bytes));
+        assert!(!has_binary_length(&bytes));
+        assert!(read_binary(&bytes, None).is_err());
+    }
+
+    #[test]
+    fn load_scene_reads_ascii_and_binary_alike() {
+        let options = LoadOptions::default();
+        let ascii = load_scene(fixture("tetrahedron.stl"), &options).unwrap();
+        let binary = load_scene(fixture("tetrahedron_binary.stl"), &options).unwrap();
+
+        // ASCII solids are named in the file, while binary ones are named after the file.
+        assert_eq!(ascii.geometries.len(), 1);
+        assert_eq!(binary.geometries.len(), 1);
+        assert_eq!(ascii.geometries[0].name.as_ref().map(String::as_str), Some("tetrahedron"));
+        assert_eq!(binary.geometries[0].name.as_ref().map(String::as_str), Some("tetrahedron_binary"));
+
+        // The mesh types don't implement `PartialEq`, so they're compared by their debug output.
+        assert_eq!(format!("{:?}", ascii.geometries[0].meshes), format!("{:?}", binary.geometries[0].meshes));
+
+        // Every facet has its own normal, so none of the corners are welded.
+        let mesh = &ascii.geometries[0].meshes[0];
+        assert_eq!(mesh.vertices.len(), 12);
+        assert_eq!(mesh.indices, (0..12).collect::<Vec<_>>());
+
+        // STL files are Z-up, so the facet facing -Z faces -Y once loaded.
+        let normal = mesh.vertices[0].normal.unwrap();
+        assert_eq!((normal.x, normal.y, normal.z), (0.0, -1.0, 0.0));
+
+        // The last facet has a zero normal in the file, so its normal comes from its winding.
+        let expected = 1.0 / 3.0f32.sqrt();
+        for vertex in &mesh.vertices[9..] {
+            let normal = vertex.normal.unwrap();
+            assert!((normal.x - expected).abs() < 1e-6);
+            assert!((normal.y - expected).abs() < 1e-6);
+            assert!((normal.z + expected).abs() < 1e-6);
+        }
+    }
+}
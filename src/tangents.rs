@@ -0,0 +1,249 @@
+//! Generating tangent frames for normal-mapped meshes.
+//!
+//! The renderer can't take tangents as vertex data yet, so normal maps aren't bound and the
+//! generated tangents are only used by `--show tangent` and `--show binormal`. They're generated
+//! for the meshes that a normal map would need them for, so that those views show the tangent
+//! frames a normal-mapping renderer would use.
+//!
+//! Per-corner tangents are projected onto the tangent plane of the vertex normal, averaged by the
+//! angle of each corner, and kept separate for triangles with mirrored texture coordinates. This
+//! is close to the tangent space most normal map bakers use, but it isn't MikkTSpace, so maps
+//! baked with MikkTSpace may show small seams. Tangents from the file are always kept.
+
+use mesh::{normalize, MeshData, Vertex};
+use polygon::math::*;
+use scene::Scene;
+use std::collections::{HashMap, HashSet};
+
+/// Generates tangents for every mesh that's drawn with a normal-mapped material but is missing
+/// tangents.
+pub fn generate_for_normal_maps(scene: &mut Scene) {
+    let mut geometry_meshes = HashSet::new();
+    let mut skin_meshes = HashSet::new();
+    let mut morph_meshes = HashSet::new();
+    {
+        let needs_tangents = |material: Option<usize>, mesh: &MeshData| {
+            material.map_or(false, |material| scene.materials[material].normal_texture.is_some())
+                && mesh.vertices.iter().any(|vertex| vertex.tangent.is_none())
+        };
+
+        for node in &scene.nodes {
+            for instance in &node.geometries {
+                for (index, mesh) in scene.geometries[instance.geometry].meshes.iter().enumerate() {
+                    if needs_tangents(instance.material_for(mesh), mesh) {
+                        geometry_meshes.insert((instance.geometry, index));
+                    }
+                }
+            }
+
+            for instance in &node.skins {
+                for (index, mesh) in scene.skins[instance.skin].meshes.iter().enumerate() {
+                    if needs_tangents(instance.material_for(mesh), mesh) {
+                        skin_meshes.insert((instance.skin, index));
+                    }
+                }
+            }
+
+            for instance in &node.morphs {
+                for (index, mesh) in scene.morphs[instance.morph].meshes.iter().enumerate() {
+                    if needs_tangents(instance.material_for(mesh), mesh) {
+                        morph_meshes.insert((instance.morph, index));
+                    }
+                }
+            }
+        }
+    }
+
+    for (geometry, index) in geometry_meshes {
+        generate(&mut scene.geometries[geometry].meshes[index]);
+    }
+
+    for (skin, index) in skin_meshes {
+        generate(&mut scene.skins[skin].meshes[index]);
+    }
+
+    // Morph targets store an offset for every vertex, so they have to follow any vertices that
+    // were split.
+    for (morph, index) in morph_meshes {
+        let sources = generate(&mut scene.morphs[morph].meshes[index]);
+        for target in &mut scene.morphs[morph].targets {
+            if let Some(deltas) = target.deltas.get_mut(index) {
+                let old_deltas = deltas.clone();
+                *deltas = sources.iter().filter_map(|&source| old_deltas.get(source).cloned()).collect();
+            }
+        }
+    }
+}
+
+/// Generates tangents and binormals for the vertices of the mesh that don't have them, based on
+/// the vertices' normals and first set of texture coordinates. Vertices that already have a
+/// tangent keep it.
+///
+/// Each binormal is the cross product of the normal and tangent, flipped for triangles whose
+/// texture coordinates are mirrored. Vertices that need tangents and are shared by mirrored and
+/// unmirrored triangles are split, so the mesh may gain vertices. Returns the index of the
+/// original vertex that each vertex of the new mesh was copied from.
+///
+/// Vertices without a normal or texture coordinates can't get a tangent, so they're left
+/// unchanged, with a warning.
+pub fn generate(mesh: &mut MeshData) -> Vec<usize> {
+    let needs_tangent = |vertex: &Vertex| vertex.tangent.is_none();
+    let can_generate = |vertex: &Vertex| vertex.normal.is_some() && !vertex.texcoord.is_empty();
+    if !mesh.vertices.iter().any(|vertex| needs_tangent(vertex)) {
+        return (0..mesh.vertices.len()).collect();
+    }
+
+    if mesh.vertices.iter().any(|vertex| needs_tangent(vertex) && !can_generate(vertex)) {
+        println!(
+            "WARNING: Some vertices of mesh with material {:?} are missing normals or texture coordinates, they won't get tangents",
+            mesh.material,
+        );
+    }
+
+    // The closures below borrow the mesh, so the new vertex data is built in its own scope.
+    let (vertices, indices, sources) = {
+        let normal = |vertex: u32| mesh.vertices[vertex as usize].normal.unwrap();
+
+        // Whether a vertex gets a generated tangent, rather than keeping its own or going without.
+        let generated = |vertex: u32| {
+            let vertex = &mesh.vertices[vertex as usize];
+            needs_tangent(vertex) && can_generate(vertex)
+        };
+
+        // Accumulate the angle-weighted tangent of every corner into its vertex, keeping mirrored
+        // and unmirrored corners apart.
+        let zero = Vector3 { x: 0.0, y: 0.0, z: 0.0 };
+        let mut sums = HashMap::<(u32, bool), Vector3>::new();
+        let mut mirrored = Vec::with_capacity(mesh.indices.len() / 3);
+        for triangle in mesh.indices.chunks(3) {
+            // Triangles with a corner that has no normal or texture coordinates have no tangent
+            // direction that can be computed.
+            if !triangle.iter().all(|&vertex| can_generate(&mesh.vertices[vertex as usize])) {
+                mirrored.push(false);
+                continue;
+            }
+
+            let position = |corner: usize| mesh.vertices[triangle[corner] as usize].position;
+            let texcoord = |corner: usize| mesh.vertices[triangle[corner] as usize].texcoord[0];
+
+            let edge_a = sub(position(1), position(0));
+            let edge_b = sub(position(2), position(0));
+            let (s_a, t_a) = (texcoord(1).x - texcoord(0).x, texcoord(1).y - texcoord(0).y);
+            let (s_b, t_b) = (texcoord(2).x - texcoord(0).x, texcoord(2).y - texcoord(0).y);
+
+            // The sign of the texture-space area tells whether the texture is mirrored on the
+            // triangle. Triangles with no texture-space area have no tangent direction of their
+            // own.
+            let area = s_a * t_b - s_b * t_a;
+            let is_mirrored = area < 0.0;
+            mirrored.push(is_mirrored);
+            if area == 0.0 {
+                continue;
+            }
+
+            let sign = if is_mirrored { -1.0 } else { 1.0 };
+            let tangent = scale(add(scale(edge_a, t_b), scale(edge_b, -t_a)), sign);
+
+            for corner in 0..3 {
+                let vertex = triangle[corner];
+                if !generated(vertex) {
+                    continue;
+                }
+
+                let projected = normalize(project(tangent, normal(vertex)));
+                let to_next = normalize(sub(position((corner + 1) % 3), position(corner)));
+                let to_previous = normalize(sub(position((corner + 2) % 3), position(corner)));
+                let angle = dot(to_next, to_previous).max(-1.0).min(1.0).acos();
+
+                let sum = sums.entry((vertex, is_mirrored)).or_insert(zero);
+                *sum = add(*sum, scale(projected, angle));
+            }
+        }
+
+        // Build the new vertices, splitting any vertex that gets a generated tangent and is used
+        // by both mirrored and unmirrored corners. Other vertices are copied unchanged.
+        let mut vertices = Vec::with_capacity(mesh.vertices.len());
+        let mut sources = Vec::with_capacity(mesh.vertices.len());
+        let mut indices = Vec::with_capacity(mesh.indices.len());
+        let mut created = HashMap::<(u32, bool), u32>::new();
+        for (face, triangle) in mesh.indices.chunks(3).enumerate() {
+            for &vertex in triangle {
+                let is_mirrored = mirrored[face] && generated(vertex);
+                let index = *created.entry((vertex, is_mirrored)).or_insert_with(|| {
+                    if !generated(vertex) {
+                        vertices.push(mesh.vertices[vertex as usize].clone());
+                        sources.push(vertex as usize);
+                        return vertices.len() as u32 - 1;
+                    }
+
+                    let normal = normal(vertex);
+                    let sum = sums.get(&(vertex, is_mirrored)).cloned().unwrap_or(zero);
+                    let mut tangent = normalize(project(sum, normal));
+                    if dot(tangent, tangent) == 0.0 {
+                        tangent = perpendicular(normal);
+                    }
+
+                    let sign = if is_mirrored { -1.0 } else { 1.0 };
+                    let mut new_vertex = mesh.vertices[vertex as usize].clone();
+                    new_vertex.tangent = Some(tangent);
+                    new_vertex.binormal = Some(scale(cross(normal, tangent), sign));
+                    vertices.push(new_vertex);
+                    sources.push(vertex as usize);
+                    vertices.len() as u32 - 1
+                });
+                indices.push(index);
+            }
+        }
+
+        (vertices, indices, sources)
+    };
+
+    mesh.vertices = vertices;
+    mesh.indices = indices;
+    sources
+}
+
+/// Removes the component of `vector` along `normal`, leaving the part in the tangent plane.
+fn project(vector: Vector3, normal: Vector3) -> Vector3 {
+    sub_vectors(vector, scale(normal, dot(vector, normal)))
+}
+
+/// Returns an arbitrary unit vector perpendicular to `normal`.
+fn perpendicular(normal: Vector3) -> Vector3 {
+    // Cross with whichever axis is least aligned with the normal to avoid a degenerate result.
+    let axis = if normal.x.abs() < 0.9 {
+        Vector3 { x: 1.0, y: 0.0, z: 0.0 }
+    } else {
+        Vector3 { x: 0.0, y: 1.0, z: 0.0 }
+    };
+
+    normalize(cross(normal, axis))
+}
+
+fn sub(a: Point, b: Point) -> Vector3 {
+    Vector3 { x: a.x - b.x, y: a.y - b.y, z: a.z - b.z }
+}
+
+fn sub_vectors(a: Vector3, b: Vector3) -> Vector3 {
+    Vector3 { x: a.x - b.x, y: a.y - b.y, z: a.z - b.z }
+}
+
+fn add(a: Vector3, b: Vector3) -> Vector3 {
+    Vector3 { x: a.x + b.x, y: a.y + b.y, z: a.z + b.z }
+}
+
+fn scale(a: Vector3, factor: f32) -> Vector3 {
+    Vector3 { x: a.x * factor, y: a.y * factor, z: a.z * factor }
+}
+
+fn dot(a: Vector3, b: Vector3) -> f32 {
+    a.x * b.x + a.y * b.y + a.z * b.z
+}
+
+fn cross(a: Vector3, b: Vector3) -> Vector3 {
+    Vector3 {
+        x: a.y * b.z - a.z * b.y,
+        y: a.z * b.x - a.x * b.z,
+        z: a.x * b.y - a.y * b.x,
+    }
+}
@@ -0,0 +1,131 @@
+//! Loading texture images referenced by mesh files.
+
+use image;
+use std::path::{Path, PathBuf};
+
+/// Decoded texture data in 8-bit RGBA format.
+#[derive(Debug, Clone)]
+pub struct Texture {
+    /// The file the texture was loaded from, or `None` for generated and embedded textures.
+    pub path: Option<PathBuf>,
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl Texture {
+    /// Loads and decodes the image at `path`.
+    ///
+    /// If the image is missing or can't be decoded, a warning is printed and a checkerboard
+    /// texture is returned instead, so that the problem is obvious in the viewer but doesn't
+    /// prevent the rest of the file from loading.
+    pub fn load(path: &Path) -> Texture {
+        match image::open(path) {
+            Ok(image) => {
+                let image = image.to_rgba();
+                Texture {
+                    path: Some(path.into()),
+                    width: image.width(),
+                    height: image.height(),
+                    data: image.into_raw(),
+                }
+            }
+
+            Err(error) => {
+                println!("WARNING: Failed to load texture {:?}, using a placeholder: {}", path, error);
+                Texture::checkerboard()
+            }
+        }
+    }
+
+    /// Decodes an image that's embedded in a mesh file, e.g. in a data URI.
+    ///
+    /// `description` identifies the image in the warning that's printed if the image can't be
+    /// decoded, in which case a checkerboard texture is returned instead.
+    pub fn from_memory(data: &[u8], description: &str) -> Texture {
+        match image::load_from_memory(data) {
+            Ok(image) => {
+                let image = image.to_rgba();
+                Texture {
+                    path: None,
+                    width: image.width(),
+                    height: image.height(),
+                    data: image.into_raw(),
+                }
+            }
+
+            Err(error) => {
+                println!("WARNING: Failed to decode {}, using a placeholder: {}", description, error);
+                Texture::checkerboard()
+            }
+        }
+    }
+
+    /// Creates a magenta and black checkerboard texture, used in place of textures that couldn't
+    /// be loaded.
+    pub fn checkerboard() -> Texture {
+        const SIZE: u32 = 8;
+
+        let mut data = Vec::with_capacity((SIZE * SIZE * 4) as usize);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                if (x + y) % 2 == 0 {
+                    data.extend_from_slice(&[255, 0, 255, 255]);
+                } else {
+                    data.extend_from_slice(&[0, 0, 0, 255]);
+                }
+            }
+        }
+
+        Texture {
+            path: None,
+            width: SIZE,
+            height: SIZE,
+            data,
+        }
+    }
+}
+
+/// Resolves a URI referenced by a mesh file into a path on disk.
+///
+/// Relative URIs are resolved relative to `base`, which should be the directory containing the
+/// mesh file. Both plain paths and `file:` URIs are supported, and percent-encoded characters are
+/// decoded.
+pub fn resolve_uri(base: &Path, uri: &str) -> PathBuf {
+    let path = if uri.starts_with("file:///") {
+        // On Windows the path after the third slash is absolute (e.g. `C:/`), elsewhere the third
+        // slash is the root of the absolute path.
+        if cfg!(windows) { &uri[8..] } else { &uri[7..] }
+    } else if uri.starts_with("file://") {
+        &uri[7..]
+    } else if uri.starts_with("file:") {
+        &uri[5..]
+    } else {
+        uri
+    };
+
+    base.join(percent_decode(path))
+}
+
+/// Decodes `%XX` escapes in a URI. Invalid escapes are left as-is.
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' && index + 2 < bytes.len() {
+            let hex = ::std::str::from_utf8(&bytes[index + 1..index + 3]).ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            if let Some(byte) = hex {
+                decoded.push(byte);
+                index += 3;
+                continue;
+            }
+        }
+
+        decoded.push(bytes[index]);
+        index += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
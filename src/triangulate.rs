@@ -0,0 +1,278 @@
+//! Triangulation of arbitrary planar polygons.
+//!
+//! Polygons are given as an ordered list of corner positions and are triangulated into a list of
+//! triangles that index into that list. Convex polygons are fan triangulated, and concave polygons
+//! are triangulated by ear clipping after being projected onto their best-fit plane. Polygons with
+//! holes are first turned into a single simple polygon by bridging each hole to the outer boundary.
+
+use polygon::math::*;
+
+/// Triangulates the polygon described by `corners`.
+///
+/// Returns a list of triangles, each of which contains three indices into `corners`. The winding
+/// of the triangles matches the winding of the original polygon. Polygons with fewer than three
+/// corners produce no triangles.
+pub fn triangulate(corners: &[Point]) -> Vec<[usize; 3]> {
+    match corners.len() {
+        0 | 1 | 2 => Vec::new(),
+        3 => vec![[0, 1, 2]],
+        _ => {
+            let normal = newell_normal(corners);
+            let projected = project(corners, normal);
+
+            if is_convex(&projected) {
+                fan(corners.len())
+            } else {
+                ear_clip(&projected, (0..corners.len()).collect())
+            }
+        }
+    }
+}
+
+/// Triangulates the polygon described by `outer` with the holes described by `holes` cut out of
+/// it.
+///
+/// The returned triangles index into the concatenation of `outer` followed by each of the holes
+/// in order, e.g. the first corner of the first hole has index `outer.len()`.
+pub fn triangulate_with_holes(outer: &[Point], holes: &[Vec<Point>]) -> Vec<[usize; 3]> {
+    if holes.is_empty() {
+        return triangulate(outer);
+    }
+
+    if outer.len() < 3 {
+        return Vec::new();
+    }
+
+    // Project all of the corners using the plane of the outer boundary so that the outer boundary
+    // is counter-clockwise.
+    let normal = newell_normal(outer);
+    let mut points = project(outer, normal);
+    let mut hole_indices = Vec::with_capacity(holes.len());
+    for hole in holes {
+        // Every hole's corners are kept so that the indices of later holes still match the
+        // concatenated corner list, but degenerate holes aren't bridged.
+        let start = points.len();
+        points.extend(project(hole, normal));
+        if hole.len() < 3 {
+            continue;
+        }
+
+        // Holes need to be wound clockwise so that the bridged polygon stays simple.
+        let mut indices = (start..points.len()).collect::<Vec<_>>();
+        if signed_area(&points, &indices) > 0.0 {
+            indices.reverse();
+        }
+
+        hole_indices.push(indices);
+    }
+
+    // Bridge holes starting with the one that reaches furthest along the X axis, so that the
+    // bridges of later holes can't cross the bridges of earlier ones.
+    hole_indices.sort_by(|left, right| {
+        let left = max_x(&points, left);
+        let right = max_x(&points, right);
+        right.partial_cmp(&left).unwrap_or(::std::cmp::Ordering::Equal)
+    });
+
+    let mut polygon = (0..outer.len()).collect::<Vec<_>>();
+    for (hole_index, hole) in hole_indices.iter().enumerate() {
+        // Start the bridge at the hole's right-most corner.
+        let (start, _) = hole.iter()
+            .enumerate()
+            .fold((0, ::std::f32::NEG_INFINITY), |(best, best_x), (position, &index)| {
+                if points[index][0] > best_x { (position, points[index][0]) } else { (best, best_x) }
+            });
+        let hole_corner = hole[start];
+
+        // Find the closest corner of the polygon that can be connected to the hole without
+        // crossing any edges of the polygon or of the holes that haven't been bridged yet.
+        let blocking_edges = edges(&polygon)
+            .chain(hole_indices[hole_index..].iter().flat_map(|hole| edges(hole)))
+            .collect::<Vec<_>>();
+        let bridge = polygon.iter()
+            .enumerate()
+            .filter(|&(_, &index)| {
+                blocking_edges.iter().all(|&(a, b)| {
+                    !segments_cross(points[hole_corner], points[index], points[a], points[b])
+                })
+            })
+            .map(|(position, &index)| (position, distance_squared(points[hole_corner], points[index])))
+            .fold(None, |best: Option<(usize, f32)>, (position, distance)| match best {
+                Some((_, best_distance)) if best_distance <= distance => best,
+                _ => Some((position, distance)),
+            });
+
+        // If the hole can't be bridged the input was degenerate, so leave the hole unfilled.
+        let bridge = match bridge {
+            Some((position, _)) => position,
+            None => { continue; }
+        };
+
+        // Splice the hole into the polygon, walking around the hole and then back across the
+        // bridge to the polygon corner where the bridge started.
+        let bridge_corner = polygon[bridge];
+        let mut spliced = Vec::with_capacity(polygon.len() + hole.len() + 2);
+        spliced.extend_from_slice(&polygon[..bridge + 1]);
+        spliced.extend(hole[start..].iter().chain(hole[..start].iter()).cloned());
+        spliced.push(hole_corner);
+        spliced.push(bridge_corner);
+        spliced.extend_from_slice(&polygon[bridge + 1..]);
+        polygon = spliced;
+    }
+
+    ear_clip(&points, polygon)
+}
+
+/// Fan triangulates a convex polygon with `count` corners around its first corner.
+pub fn fan(count: usize) -> Vec<[usize; 3]> {
+    (1..count.saturating_sub(1))
+        .map(|index| [0, index, index + 1])
+        .collect()
+}
+
+/// Computes the (unnormalized) normal of the polygon using Newell's method, which gives a robust
+/// result for non-planar and concave polygons.
+fn newell_normal(corners: &[Point]) -> [f32; 3] {
+    let mut normal = [0.0, 0.0, 0.0];
+    for (index, current) in corners.iter().enumerate() {
+        let next = &corners[(index + 1) % corners.len()];
+        normal[0] += (current.y - next.y) * (current.z + next.z);
+        normal[1] += (current.z - next.z) * (current.x + next.x);
+        normal[2] += (current.x - next.x) * (current.y + next.y);
+    }
+
+    normal
+}
+
+/// Projects the corners onto the coordinate plane most perpendicular to `normal`, flipping the
+/// result as necessary so that the projected polygon is wound counter-clockwise.
+fn project(corners: &[Point], normal: [f32; 3]) -> Vec<[f32; 2]> {
+    let abs = [normal[0].abs(), normal[1].abs(), normal[2].abs()];
+
+    // Drop the axis with the largest normal component, and pick the remaining two axes in an
+    // order that preserves the polygon's winding.
+    let (u, v, flip) = if abs[0] >= abs[1] && abs[0] >= abs[2] {
+        (1, 2, normal[0] < 0.0)
+    } else if abs[1] >= abs[2] {
+        (2, 0, normal[1] < 0.0)
+    } else {
+        (0, 1, normal[2] < 0.0)
+    };
+
+    corners.iter()
+        .map(|corner| {
+            let components = [corner.x, corner.y, corner.z];
+            if flip {
+                [components[v], components[u]]
+            } else {
+                [components[u], components[v]]
+            }
+        })
+        .collect()
+}
+
+/// Returns twice the signed area of the triangle `a`, `b`, `c`. The result is positive if the
+/// triangle is wound counter-clockwise.
+fn cross(a: [f32; 2], b: [f32; 2], c: [f32; 2]) -> f32 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+/// Returns `true` if no corner of the counter-clockwise polygon turns clockwise.
+fn is_convex(points: &[[f32; 2]]) -> bool {
+    let count = points.len();
+    (0..count).all(|index| {
+        let previous = points[(index + count - 1) % count];
+        let next = points[(index + 1) % count];
+        cross(previous, points[index], next) >= 0.0
+    })
+}
+
+/// Returns `true` if `point` lies inside or on the edge of the counter-clockwise triangle `a`,
+/// `b`, `c`.
+fn in_triangle(point: [f32; 2], a: [f32; 2], b: [f32; 2], c: [f32; 2]) -> bool {
+    cross(a, b, point) >= 0.0 && cross(b, c, point) >= 0.0 && cross(c, a, point) >= 0.0
+}
+
+/// Returns twice the signed area of the polygon made up of the given corners of `points`.
+fn signed_area(points: &[[f32; 2]], polygon: &[usize]) -> f32 {
+    edges(polygon)
+        .map(|(a, b)| points[a][0] * points[b][1] - points[b][0] * points[a][1])
+        .sum()
+}
+
+/// Returns the largest X coordinate of the given corners of `points`.
+fn max_x(points: &[[f32; 2]], polygon: &[usize]) -> f32 {
+    polygon.iter().fold(::std::f32::NEG_INFINITY, |max, &index| max.max(points[index][0]))
+}
+
+/// Returns an iterator over the edges of the closed polygon as pairs of corner indices.
+fn edges<'a>(polygon: &'a [usize]) -> impl Iterator<Item = (usize, usize)> + 'a {
+    polygon.iter().cloned().zip(polygon.iter().cycle().skip(1).cloned())
+}
+
+fn distance_squared(a: [f32; 2], b: [f32; 2]) -> f32 {
+    (a[0] - b[0]) * (a[0] - b[0]) + (a[1] - b[1]) * (a[1] - b[1])
+}
+
+/// Returns `true` if the segments `a0`-`a1` and `b0`-`b1` cross. Segments that only touch at a
+/// shared endpoint don't count as crossing.
+fn segments_cross(a0: [f32; 2], a1: [f32; 2], b0: [f32; 2], b1: [f32; 2]) -> bool {
+    if a0 == b0 || a0 == b1 || a1 == b0 || a1 == b1 {
+        return false;
+    }
+
+    let d0 = cross(a0, a1, b0);
+    let d1 = cross(a0, a1, b1);
+    let d2 = cross(b0, b1, a0);
+    let d3 = cross(b0, b1, a1);
+    (d0 > 0.0) != (d1 > 0.0) && (d2 > 0.0) != (d3 > 0.0)
+}
+
+/// Triangulates a simple, counter-clockwise polygon by repeatedly clipping ears.
+///
+/// `polygon` lists the polygon's corners as indices into `points`. The same index may appear more
+/// than once, which is the case for polygons that have had holes bridged into them.
+///
+/// If the polygon is degenerate (e.g. self-intersecting) and no ear can be found, the remaining
+/// corners are fan triangulated so that every corner still ends up in the output.
+fn ear_clip(points: &[[f32; 2]], polygon: Vec<usize>) -> Vec<[usize; 3]> {
+    let mut remaining = polygon;
+    let mut triangles = Vec::with_capacity(remaining.len().saturating_sub(2));
+
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let ear = (0..count).find(|&index| {
+            let previous = remaining[(index + count - 1) % count];
+            let current = remaining[index];
+            let next = remaining[(index + 1) % count];
+            let (a, b, c) = (points[previous], points[current], points[next]);
+
+            // Reflex corners can't be ears.
+            if cross(a, b, c) <= 0.0 {
+                return false;
+            }
+
+            // The corner is an ear if no other corner lies inside its triangle.
+            remaining.iter()
+                .filter(|&&other| other != previous && other != current && other != next)
+                .all(|&other| !in_triangle(points[other], a, b, c))
+        });
+
+        match ear {
+            Some(index) => {
+                let previous = remaining[(index + count - 1) % count];
+                let next = remaining[(index + 1) % count];
+                triangles.push([previous, remaining[index], next]);
+                remaining.remove(index);
+            }
+
+            None => { break; }
+        }
+    }
+
+    for triangle in fan(remaining.len()) {
+        triangles.push([remaining[triangle[0]], remaining[triangle[1]], remaining[triangle[2]]]);
+    }
+
+    triangles
+}